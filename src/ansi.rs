@@ -84,34 +84,30 @@ fn apply_sgr_params(params: &[u16], style: &mut Style) {
             40..=47 => *style = style.bg(basic_color(params[pi] - 40)),
             90..=97 => *style = style.fg(bright_color(params[pi] - 90)),
             100..=107 => *style = style.bg(bright_color(params[pi] - 100)),
-            38 => {
-                if pi + 1 < params.len() {
-                    if params[pi + 1] == 5 && pi + 2 < params.len() {
-                        *style = style.fg(Color::Indexed(params[pi + 2] as u8));
-                        pi += 2;
-                    } else if params[pi + 1] == 2 && pi + 4 < params.len() {
-                        *style = style.fg(Color::Rgb(
-                            params[pi + 2] as u8,
-                            params[pi + 3] as u8,
-                            params[pi + 4] as u8,
-                        ));
-                        pi += 4;
-                    }
+            38 if pi + 1 < params.len() => {
+                if params[pi + 1] == 5 && pi + 2 < params.len() {
+                    *style = style.fg(Color::Indexed(params[pi + 2] as u8));
+                    pi += 2;
+                } else if params[pi + 1] == 2 && pi + 4 < params.len() {
+                    *style = style.fg(Color::Rgb(
+                        params[pi + 2] as u8,
+                        params[pi + 3] as u8,
+                        params[pi + 4] as u8,
+                    ));
+                    pi += 4;
                 }
             }
-            48 => {
-                if pi + 1 < params.len() {
-                    if params[pi + 1] == 5 && pi + 2 < params.len() {
-                        *style = style.bg(Color::Indexed(params[pi + 2] as u8));
-                        pi += 2;
-                    } else if params[pi + 1] == 2 && pi + 4 < params.len() {
-                        *style = style.bg(Color::Rgb(
-                            params[pi + 2] as u8,
-                            params[pi + 3] as u8,
-                            params[pi + 4] as u8,
-                        ));
-                        pi += 4;
-                    }
+            48 if pi + 1 < params.len() => {
+                if params[pi + 1] == 5 && pi + 2 < params.len() {
+                    *style = style.bg(Color::Indexed(params[pi + 2] as u8));
+                    pi += 2;
+                } else if params[pi + 1] == 2 && pi + 4 < params.len() {
+                    *style = style.bg(Color::Rgb(
+                        params[pi + 2] as u8,
+                        params[pi + 3] as u8,
+                        params[pi + 4] as u8,
+                    ));
+                    pi += 4;
                 }
             }
             39 => *style = style.fg(Color::Reset),
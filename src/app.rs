@@ -1,9 +1,62 @@
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::Rect;
+use ratatui::text::Line;
+use rusqlite::Connection;
+
+use crate::cache::{CacheKey, PreviewCache};
+use crate::config::Config;
+use crate::graphics::{ImageProtocol, Placement};
+use crate::keymap::{Action, KeyChord, Lookup};
+use crate::markdown::Document;
+use crate::palette::{PaletteCommand, PaletteEntry, PaletteState};
 use crate::preview::{PreviewContent, Previewer};
-use crate::tree::{self, TreeNode, VisibleRow};
+use crate::stream::LineIndex;
+use crate::sqlite::{self, Database};
+use crate::structured::Tree;
+use crate::syntax;
+use crate::table::Table;
+use crate::theme;
+use crate::tree::{self, SortMode, TreeNode, TreeOptions, VisibleRow};
+use crate::vfs::{LocalVfs, Vfs};
+use crate::worker::PreviewWorker;
+
+/// Moves closer together than this count as one, and only the file where
+/// they stop is previewed
+const PREVIEW_DEBOUNCE: Duration = Duration::from_millis(40);
+
+/// Previews ready this quickly are shown without a "Loading" frame first
+const PREVIEW_WAIT: Duration = Duration::from_millis(15);
+
+/// State of follow mode (`F`): the followed file as it was last seen
+pub struct FollowState {
+    pub path: PathBuf,
+    id: Option<u64>,
+    size: u64,
+    /// Keep the view at the end as the file grows
+    pinned: bool,
+}
+
+/// Rows of a database table fetched for display, with the window they were
+/// fetched for so they're only read again when it changes
+pub struct DbPage {
+    pub table: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub rows: Result<Table, String>,
+}
+
+/// State of the `?` help overlay
+#[derive(Default)]
+pub struct HelpState {
+    pub query: String,
+    pub scroll: usize,
+}
 
 pub struct App {
     pub root_path: PathBuf,
@@ -11,35 +64,156 @@ pub struct App {
     pub expanded: HashSet<PathBuf>,
     pub visible_rows: Vec<VisibleRow>,
     pub selected_index: usize,
-    pub show_hidden: bool,
+    pub config: Config,
+    pub tree_options: TreeOptions,
+    pub show_preview: bool,
+    pub split_percent: u16, // width of the tree pane when the preview is shown
     pub preview_scroll: usize,
     pub preview_cache: (PreviewContent, usize),
+    pub image_protocol: ImageProtocol, // resolved from the config, never `Auto`
+    pub status_message: Option<String>, // shown in place of the key hints
+    pub pending_keys: Vec<KeyChord>,    // start of a multi-key binding
+    pub help: Option<HelpState>,
+    pub palette: Option<PaletteState>,
+    pub follow: Option<FollowState>,
+    pub show_debug: bool, // cache statistics in the status bar
+    pub link_focus: Option<usize>, // selected link of a markdown preview
+    pub node_cursor: usize, // selected node of a JSON/YAML/TOML preview
+    pub folded: HashSet<usize>, // folded nodes of it
+    pub table_column: usize, // first column shown of a table preview
+    pub db_cursor: usize, // selected object of a database preview
+    pub db_table: Option<usize>, // object whose rows are shown instead of the schema
+    db_schema_scroll: usize, // where the schema was scrolled to, for going back to it
+    pub db_page: Option<DbPage>, // rows of the open table on screen
+    db_connection: Option<(PathBuf, Connection)>, // kept open while a table is shown
     pub should_quit: bool,
+    pub vfs: Arc<dyn Vfs>, // where the tree and previews are read from
     previewer: Previewer,
+    worker: PreviewWorker,
+    cache: PreviewCache,
+    pending_key: Option<CacheKey>, // cache key of the preview being generated
     last_preview_path: Option<PathBuf>,
+    preview_due: Option<Instant>, // when to request the preview of `last_preview_path`
+    last_navigation: Option<Instant>,
+    pending_anchor: Option<String>, // heading to scroll to once the preview is ready
 }
 
 impl App {
-    pub fn new(root_path: PathBuf) -> Self {
-        let tree = tree::build_tree(&root_path);
-        let expanded = HashSet::new();
-        let previewer = Previewer::new();
-
+    pub fn new(root_path: PathBuf, config: Config) -> Result<Self, String> {
+        let (syntax_set, syntax_errors) = match syntax::user_syntax_dir() {
+            Some(dir) => syntax::load_syntaxes(&dir),
+            None => (two_face::syntax::extra_newlines(), Vec::new()),
+        };
         let mut app = App {
             root_path,
-            tree,
-            expanded,
+            tree: Vec::new(),
+            expanded: HashSet::new(),
             visible_rows: Vec::new(),
             selected_index: 0,
-            show_hidden: false,
+            tree_options: config.tree_options(),
+            show_preview: config.layout.show_preview,
+            split_percent: config.layout.split,
+            config: Config::default(),
             preview_scroll: 0,
             preview_cache: (PreviewContent::Empty, 0),
+            image_protocol: config.preview.image_protocol.resolve(),
+            status_message: None,
+            pending_keys: Vec::new(),
+            help: None,
+            palette: None,
+            follow: None,
+            show_debug: false,
+            link_focus: None,
+            node_cursor: 0,
+            folded: HashSet::new(),
+            table_column: 0,
+            db_cursor: 0,
+            db_table: None,
+            db_schema_scroll: 0,
+            db_page: None,
+            db_connection: None,
             should_quit: false,
-            previewer,
+            vfs: Arc::new(LocalVfs::default()),
+            previewer: Previewer::new(syntax_set),
+            worker: PreviewWorker::new(),
+            cache: PreviewCache::new(config.preview.cache_bytes),
+            pending_key: None,
             last_preview_path: None,
+            preview_due: None,
+            last_navigation: None,
+            pending_anchor: None,
+        };
+        app.load_user_themes();
+        app.apply_config(config)?;
+        if let Some(first) = syntax_errors.first() {
+            app.status_message = Some(format!("Failed to load syntax {}", first));
+        }
+        Ok(app)
+    }
+
+    /// Add the `.tmTheme` files in the config directory to the built-in
+    /// themes. A file that fails to load is reported in the status bar.
+    fn load_user_themes(&mut self) {
+        let Some(dir) = theme::user_theme_dir() else {
+            return;
+        };
+        let (themes, errors) = theme::load_user_themes(&dir);
+        for (name, theme) in themes {
+            self.previewer.add_theme(name, theme);
+        }
+        if let Some(first) = errors.first() {
+            self.status_message = Some(format!("Failed to load theme {}", first));
+        }
+    }
+
+    /// Apply (or re-apply after a reload) config settings. Fails without
+    /// changing anything if the configured theme or a mapped syntax doesn't exist.
+    pub fn apply_config(&mut self, config: Config) -> Result<(), String> {
+        let name = theme::resolve(&config.preview, |name| self.previewer.has_theme(name));
+        if !self.previewer.has_theme(&name) {
+            return Err(format!(
+                "unknown theme '{}' (available: {})",
+                name,
+                self.previewer.theme_names().join(", ")
+            ));
+        }
+        if self.previewer.set_syntax_mappings(&config.syntax)? {
+            // Cached previews were highlighted with the old mappings
+            self.cache.clear();
+        }
+        self.previewer.set_theme(&name);
+        self.previewer.set_max_bytes(config.preview.max_bytes);
+        self.cache.set_max_bytes(config.preview.cache_bytes);
+        self.tree_options = TreeOptions {
+            revealed: self.tree_options.revealed.take(),
+            ..config.tree_options()
         };
-        app.refresh();
-        app
+        self.show_preview = config.layout.show_preview;
+        self.split_percent = config.layout.split;
+        self.image_protocol = config.preview.image_protocol.resolve();
+        self.config = config;
+
+        self.refresh();
+        self.reload_preview();
+        Ok(())
+    }
+
+    /// The previewed image to draw over `area` with a graphics protocol,
+    /// if there is one and half blocks aren't used instead
+    pub fn image_placement(&self, area: Rect) -> Option<Placement<'_>> {
+        match &self.preview_cache.0 {
+            PreviewContent::Image { path, image, .. }
+                if self.image_protocol != ImageProtocol::Halfblocks =>
+            {
+                Some(Placement {
+                    path,
+                    image,
+                    area,
+                    protocol: self.image_protocol,
+                })
+            }
+            _ => None,
+        }
     }
 
     pub fn display_root(&self) -> String {
@@ -57,9 +231,9 @@ impl App {
 
     /// Rebuild tree from disk and flatten, then update preview
     pub fn refresh(&mut self) {
-        self.tree = tree::build_tree(&self.root_path);
+        self.tree = tree::build_tree(self.vfs.as_ref(), &self.root_path, &self.tree_options);
         self.visible_rows =
-            tree::flatten_tree(&mut self.tree, &self.expanded, self.show_hidden);
+            tree::flatten_tree(self.vfs.as_ref(), &mut self.tree, &self.expanded, &self.tree_options);
 
         // Clamp selected index
         if self.visible_rows.is_empty() {
@@ -71,18 +245,545 @@ impl App {
         self.update_preview();
     }
 
+    /// Feed a key press through the keymap, running the bound action once a
+    /// complete sequence has been typed
+    pub fn handle_key(&mut self, key: KeyEvent, page_height: u16) {
+        if self.palette.is_some() {
+            self.handle_palette_key(key, page_height);
+            return;
+        }
+        if self.help.is_some() {
+            self.handle_help_key(key, page_height);
+            return;
+        }
+
+        let chord = KeyChord::from(key);
+        if chord.code == KeyCode::Esc && !self.pending_keys.is_empty() {
+            self.pending_keys.clear();
+            return;
+        }
+
+        self.pending_keys.push(chord);
+        match self.config.keymap.lookup(&self.pending_keys) {
+            Lookup::Action(action) => {
+                self.pending_keys.clear();
+                self.perform(action, page_height);
+            }
+            Lookup::Prefix => {}
+            Lookup::None => {
+                // An unfinished sequence followed by an unrelated key:
+                // drop the sequence and try the key on its own
+                let retry = self.pending_keys.len() > 1;
+                self.pending_keys.clear();
+                if retry {
+                    self.handle_key(key, page_height);
+                }
+            }
+        }
+    }
+
+    /// Keys typed while the help overlay is open edit its search query;
+    /// navigation keys scroll it and Esc closes it
+    fn handle_help_key(&mut self, key: KeyEvent, page_height: u16) {
+        let Some(help) = self.help.as_mut() else {
+            return;
+        };
+        let half_page = (page_height / 2) as usize;
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, _) if !help.query.is_empty() => {
+                help.query.clear();
+                help.scroll = 0;
+            }
+            (KeyCode::Esc | KeyCode::Enter, _) => self.help = None,
+            (KeyCode::Char('?'), _) if help.query.is_empty() => self.help = None,
+            (KeyCode::Char('c'), KeyModifiers::CONTROL) => self.help = None,
+            (KeyCode::Down, _) | (KeyCode::Char('n'), KeyModifiers::CONTROL) => help.scroll += 1,
+            (KeyCode::Up, _) | (KeyCode::Char('p'), KeyModifiers::CONTROL) => {
+                help.scroll = help.scroll.saturating_sub(1);
+            }
+            (KeyCode::PageDown, _) => help.scroll += half_page,
+            (KeyCode::PageUp, _) => help.scroll = help.scroll.saturating_sub(half_page),
+            (KeyCode::Backspace, _) => {
+                help.query.pop();
+                help.scroll = 0;
+            }
+            (KeyCode::Char(c), m) if !m.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                help.query.push(c);
+                help.scroll = 0;
+            }
+            _ => {}
+        }
+    }
+
+    /// Keys typed in the command palette edit its query; arrows move the
+    /// selection, Enter runs the selected entry and Esc closes it
+    fn handle_palette_key(&mut self, key: KeyEvent, page_height: u16) {
+        let Some(palette) = self.palette.as_mut() else {
+            return;
+        };
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, _) | (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
+                if let Some(original) = palette.original_scroll {
+                    self.preview_scroll = original;
+                }
+                if let Some(original) = palette.original_theme.take()
+                    && self.previewer.theme_name() != original
+                {
+                    self.set_theme(&original);
+                }
+                self.palette = None;
+            }
+            (KeyCode::Enter, _) => {
+                let command = palette.selected_command();
+                self.palette = None;
+                if let Some(command) = command {
+                    self.run_palette_command(command, page_height);
+                }
+            }
+            (KeyCode::Down, _) | (KeyCode::Tab, _) | (KeyCode::Char('n'), KeyModifiers::CONTROL) => {
+                palette.move_selection(true);
+            }
+            (KeyCode::Up, _)
+            | (KeyCode::BackTab, _)
+            | (KeyCode::Char('p'), KeyModifiers::CONTROL) => palette.move_selection(false),
+            (KeyCode::Backspace, _) => {
+                let mut query = palette.query.clone();
+                query.pop();
+                palette.set_query(query);
+            }
+            (KeyCode::Char(c), m) if !m.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                let query = format!("{}{}", palette.query, c);
+                palette.set_query(query);
+            }
+            _ => {}
+        }
+        self.preview_selection();
+    }
+
+    /// In the theme picker, show the preview in the selected theme; in the
+    /// outline, scroll it to the selected heading
+    fn preview_selection(&mut self) {
+        let Some(palette) = self.palette.as_ref() else {
+            return;
+        };
+        match palette.selected_command() {
+            Some(PaletteCommand::Theme(name))
+                if palette.is_theme_picker() && name != self.previewer.theme_name() =>
+            {
+                self.set_theme(&name);
+            }
+            Some(PaletteCommand::Heading(line)) if palette.is_outline() => self.preview_scroll = line,
+            _ => {}
+        }
+    }
+
+    /// Open the palette with every action plus sort and theme choices
+    fn open_palette(&mut self) {
+        let mut entries: Vec<PaletteEntry> = Action::ALL
+            .iter()
+            .filter(|&&action| action != Action::CommandPalette)
+            .map(|&action| PaletteEntry {
+                label: action.description().to_string(),
+                keys: self.config.keymap.keys_for(action).join(", "),
+                command: PaletteCommand::Action(action),
+                active: false,
+            })
+            .collect();
+        entries.extend(SortMode::ALL.iter().map(|&mode| PaletteEntry {
+            label: format!("Sort by {}", mode.name()),
+            keys: String::new(),
+            command: PaletteCommand::Sort(mode),
+            active: mode == self.tree_options.sort,
+        }));
+        entries.extend(self.theme_entries("Theme: "));
+        self.palette = Some(PaletteState::new(entries));
+    }
+
+    /// Open the palette listing only themes
+    fn open_theme_picker(&mut self) {
+        let entries = self.theme_entries("");
+        let original = self.previewer.theme_name().to_string();
+        self.palette = Some(PaletteState::theme_picker(entries, original));
+    }
+
+    /// Open the palette listing the headings of the markdown preview
+    fn open_outline(&mut self) {
+        let Some(document) = self.document().cloned() else {
+            self.status_message = Some("No outline: not a markdown preview".to_string());
+            return;
+        };
+        if document.headings.is_empty() {
+            self.status_message = Some("No headings".to_string());
+            return;
+        }
+        let current = document.section(self.preview_scroll).last().map(|h| h.line);
+        let entries = document
+            .headings
+            .iter()
+            .map(|heading| PaletteEntry {
+                label: format!("{}{}", "  ".repeat(heading.level as usize - 1), heading.text),
+                keys: String::new(),
+                command: PaletteCommand::Heading(heading.line),
+                active: Some(heading.line) == current,
+            })
+            .collect();
+        self.palette = Some(PaletteState::outline(entries, self.preview_scroll));
+    }
+
+    fn theme_entries(&self, prefix: &str) -> Vec<PaletteEntry> {
+        self.previewer
+            .theme_names()
+            .into_iter()
+            .map(|name| PaletteEntry {
+                label: format!("{}{}", prefix, name),
+                keys: String::new(),
+                command: PaletteCommand::Theme(name.to_string()),
+                active: name == self.previewer.theme_name(),
+            })
+            .collect()
+    }
+
+    fn run_palette_command(&mut self, command: PaletteCommand, page_height: u16) {
+        match command {
+            PaletteCommand::Action(action) => self.perform(action, page_height),
+            PaletteCommand::Sort(mode) => self.set_sort(mode),
+            PaletteCommand::Theme(name) => self.choose_theme(&name),
+            PaletteCommand::Heading(line) => self.preview_scroll = line,
+        }
+    }
+
+    pub fn scroll_help(&mut self, down: bool) {
+        let amount = self.config.preview.mouse_scroll_lines;
+        if let Some(help) = self.help.as_mut() {
+            help.scroll = if down {
+                help.scroll + amount
+            } else {
+                help.scroll.saturating_sub(amount)
+            };
+        }
+    }
+
+    pub fn perform(&mut self, action: Action, page_height: u16) {
+        let half_page = (page_height / 2) as usize;
+        match action {
+            Action::Quit => self.should_quit = true,
+            Action::MoveDown => self.move_down(),
+            Action::MoveUp => self.move_up(),
+            Action::JumpTop => self.jump_top(),
+            Action::JumpBottom => self.jump_bottom(),
+            Action::ToggleExpand => self.toggle_expand(),
+            Action::CollapseOrParent => self.collapse_or_parent(),
+            Action::ToggleHidden => self.toggle_hidden(),
+            Action::Refresh => self.refresh(),
+            Action::YankPath => self.yank_path(),
+            // In a JSON/YAML/TOML tree or a database schema these move the
+            // selection instead
+            Action::ScrollPreviewDown | Action::ScrollPreviewUp
+            | Action::ScrollPreviewHalfDown | Action::ScrollPreviewHalfUp
+            | Action::ScrollPreviewTop | Action::ScrollPreviewBottom
+                if self.structure().is_some() || (self.database().is_some() && self.db_table.is_none()) =>
+            {
+                let lines = self.config.preview.scroll_lines as isize;
+                let delta = match action {
+                    Action::ScrollPreviewDown => lines,
+                    Action::ScrollPreviewUp => -lines,
+                    Action::ScrollPreviewHalfDown => half_page as isize,
+                    Action::ScrollPreviewHalfUp => -(half_page as isize),
+                    Action::ScrollPreviewTop => isize::MIN,
+                    _ => isize::MAX,
+                };
+                if self.structure().is_some() {
+                    self.move_node_cursor(delta, page_height);
+                } else {
+                    self.move_db_cursor(delta, page_height);
+                }
+            }
+            Action::ScrollPreviewDown => self.scroll_preview_down(self.config.preview.scroll_lines),
+            Action::ScrollPreviewUp => self.scroll_preview_up(self.config.preview.scroll_lines),
+            Action::ScrollPreviewHalfDown => self.scroll_preview_down(half_page),
+            Action::ScrollPreviewHalfUp => self.scroll_preview_up(half_page),
+            Action::ScrollPreviewTop => self.preview_scroll = 0,
+            Action::ScrollPreviewBottom => self.scroll_preview_to_end(page_height),
+            Action::ScrollPreviewLeft => self.table_column = self.table_column.saturating_sub(1),
+            Action::ScrollPreviewRight => {
+                let columns = match &self.preview_cache.0 {
+                    PreviewContent::Table(table) => table.column_count(),
+                    PreviewContent::Database(database) => self
+                        .db_table
+                        .map_or(0, |i| database.objects[i].columns),
+                    _ => 0,
+                };
+                self.table_column = (self.table_column + 1).min(columns.saturating_sub(1));
+            }
+            Action::ToggleFollow => self.toggle_follow(page_height),
+            Action::ToggleIgnore => self.toggle_ignore(),
+            Action::CycleSort => {
+                let modes = SortMode::ALL;
+                let current = modes.iter().position(|&m| m == self.tree_options.sort);
+                let next = current.map_or(0, |i| (i + 1) % modes.len());
+                self.set_sort(modes[next]);
+            }
+            Action::TogglePreview => self.show_preview = !self.show_preview,
+            Action::GrowTree => self.resize_tree(5),
+            Action::ShrinkTree => self.resize_tree(-5),
+            Action::ShowHelp => self.help = Some(HelpState::default()),
+            Action::CommandPalette => self.open_palette(),
+            Action::PickTheme => self.open_theme_picker(),
+            Action::NextLink => self.focus_link(true, page_height),
+            Action::PrevLink => self.focus_link(false, page_height),
+            Action::FollowLink => self.follow_link(),
+            Action::Outline => self.open_outline(),
+            Action::ToggleNode => self.toggle_node(),
+            Action::CollapseNodes => self.fold_all(true),
+            Action::ExpandNodes => self.fold_all(false),
+            Action::ToggleDebug => self.show_debug = !self.show_debug,
+        }
+    }
+
+    /// Width of the tree pane for a terminal `total_width` columns wide
+    pub fn tree_width(&self, total_width: u16) -> u16 {
+        if self.show_preview {
+            total_width * self.split_percent / 100
+        } else {
+            total_width
+        }
+    }
+
+    pub fn set_sort(&mut self, mode: SortMode) {
+        self.tree_options.sort = mode;
+        self.status_message = Some(format!("Sorted by {}", mode.name()));
+        self.refresh();
+    }
+
+    /// Switch the syntax theme and re-render the current preview
+    pub fn set_theme(&mut self, name: &str) -> bool {
+        if !self.previewer.set_theme(name) {
+            return false;
+        }
+        self.status_message = Some(format!("Theme: {}", name));
+        self.reload_preview();
+        true
+    }
+
+    /// Switch to `name` and remember it for the next start (when
+    /// `preview.theme` is "auto")
+    fn choose_theme(&mut self, name: &str) {
+        if !self.set_theme(name) {
+            return;
+        }
+        if let Err(e) = theme::save(name) {
+            self.status_message = Some(format!("Theme: {} (not saved: {})", name, e));
+        } else if self.config.preview.theme != theme::AUTO {
+            self.status_message = Some(format!("Theme: {} (the config's preview.theme applies at startup)", name));
+        }
+    }
+
+    /// Change the tree pane width by `delta` percentage points
+    fn resize_tree(&mut self, delta: i16) {
+        self.show_preview = true;
+        self.split_percent = (self.split_percent as i16 + delta).clamp(10, 90) as u16;
+    }
+
+    /// Expand all directories down to `depth` levels below the root
+    pub fn expand_to_depth(&mut self, depth: usize) {
+        for level in 0..depth {
+            let dirs: Vec<PathBuf> = self
+                .visible_rows
+                .iter()
+                .filter(|r| r.is_directory && r.depth == level)
+                .map(|r| r.path.clone())
+                .collect();
+            if dirs.is_empty() {
+                break;
+            }
+            self.expanded.extend(dirs);
+            self.refresh();
+        }
+    }
+
+    /// Schedule a preview of the selected entry if the selection moved.
+    /// The first move after a pause is previewed right away; during rapid
+    /// navigation the preview waits until the selection settles.
     fn update_preview(&mut self) {
         let current_path = self
             .visible_rows
             .get(self.selected_index)
             .map(|r| r.path.clone());
+        if current_path == self.last_preview_path {
+            return;
+        }
 
-        if current_path != self.last_preview_path {
-            self.last_preview_path = current_path.clone();
-            if let Some(path) = current_path {
-                self.preview_cache = self.previewer.preview(&path);
-            } else {
-                self.preview_cache = (PreviewContent::Empty, 0);
+        if self
+            .follow
+            .as_ref()
+            .is_some_and(|f| Some(&f.path) != current_path.as_ref())
+        {
+            self.follow = None;
+        }
+        self.link_focus = None;
+        self.pending_anchor = None;
+        self.node_cursor = 0;
+        self.folded.clear();
+        self.table_column = 0;
+        self.db_cursor = 0;
+        self.db_table = None;
+        self.db_page = None;
+        self.db_connection = None;
+        self.worker.cancel();
+        self.preview_cache = match current_path {
+            Some(_) => (PreviewContent::Loading, 1),
+            None => (PreviewContent::Empty, 0),
+        };
+        self.last_preview_path = current_path;
+
+        let now = Instant::now();
+        let settled = self
+            .last_navigation
+            .is_none_or(|last| now.duration_since(last) >= PREVIEW_DEBOUNCE);
+        self.last_navigation = Some(now);
+        self.preview_due = Some(if settled { now } else { now + PREVIEW_DEBOUNCE });
+    }
+
+    /// Generate the current preview again (after a settings change, or when
+    /// a followed file changed), keeping the old one on screen meanwhile
+    fn reload_preview(&mut self) {
+        self.preview_due = Some(Instant::now());
+    }
+
+    /// Work done between events: start a scheduled preview (from the cache
+    /// if possible), take in finished ones and check a followed file
+    pub fn tick(&mut self, width: u16, page_height: u16) {
+        // A hidden preview keeps its width, so showing it again doesn't
+        // re-render anything
+        let preview_width = width
+            .saturating_sub(self.tree_width(width))
+            .saturating_sub(1); // border
+        if self.show_preview && preview_width != self.previewer.width() {
+            self.previewer.set_width(preview_width);
+            self.reload_preview();
+        }
+
+        let mut wait = Duration::ZERO;
+        if let Some(due) = self.preview_due
+            && Instant::now() >= due
+        {
+            self.preview_due = None;
+            if let Some(path) = self.last_preview_path.clone() {
+                let key = self.cache_key(&path);
+                match key.as_ref().and_then(|key| self.cache.get(key)) {
+                    Some(preview) => {
+                        self.worker.cancel();
+                        self.show_result(preview, page_height);
+                    }
+                    None => {
+                        self.worker.request(self.previewer.clone(), self.vfs.clone(), &path);
+                        self.pending_key = key;
+                        wait = PREVIEW_WAIT;
+                    }
+                }
+            }
+        }
+
+        while let Some(response) = self.worker.recv(wait) {
+            wait = Duration::ZERO;
+            if response.done
+                && let Some(key) = self.pending_key.take()
+            {
+                self.cache.insert(key, &response.content);
+            }
+            self.show_result(response.content, page_height);
+        }
+
+        self.poll_follow(page_height);
+        self.fetch_db_page(page_height);
+    }
+
+    fn show_result(&mut self, preview: (PreviewContent, usize), page_height: u16) {
+        self.preview_cache = preview;
+        // The database may have changed (or been replaced) on disk
+        self.db_page = None;
+        self.db_connection = None;
+        if self.follow.as_ref().is_some_and(|f| f.pinned) {
+            self.scroll_preview_to_end(page_height);
+        }
+        if let PreviewContent::Markdown(document) = &self.preview_cache.0 {
+            if self.link_focus.is_some_and(|i| i >= document.links.len()) {
+                self.link_focus = None;
+            }
+            if let Some(anchor) = self.pending_anchor.take() {
+                self.scroll_to_anchor(&anchor);
+            }
+        }
+        // A reloaded file may have fewer nodes or tables
+        if let PreviewContent::Structured(tree) = &self.preview_cache.0 {
+            let count = tree.nodes.len();
+            self.folded.retain(|&i| i < count);
+            if self.node_cursor >= count {
+                self.node_cursor = 0;
+            }
+        }
+        if let PreviewContent::Database(database) = &self.preview_cache.0
+            && self.db_cursor >= database.objects.len()
+        {
+            self.db_cursor = 0;
+            self.db_table = None;
+        }
+    }
+
+    /// What the preview of `path` depends on, or None if it can't be read
+    fn cache_key(&self, path: &Path) -> Option<CacheKey> {
+        let metadata = self.vfs.metadata(path).ok()?;
+        Some(CacheKey {
+            path: path.to_path_buf(),
+            modified: metadata.modified,
+            size: metadata.len,
+            width: self.previewer.width(),
+            theme: self.previewer.theme_name().to_string(),
+            max_bytes: self.previewer.max_bytes(),
+        })
+    }
+
+    /// Preview cache statistics for the debug view
+    pub fn debug_info(&self) -> String {
+        self.cache.stats()
+    }
+
+    /// Whether a preview is scheduled or being generated, so the main loop
+    /// should check back soon
+    pub fn is_busy(&self) -> bool {
+        self.preview_due.is_some() || self.worker.is_busy()
+    }
+
+    /// Expand every directory between the root and `target`, select it and
+    /// optionally scroll its preview so `line` (1-based) is at the top
+    pub fn reveal(&mut self, target: &Path, line: Option<usize>) {
+        let Ok(relative) = target.strip_prefix(&self.root_path) else {
+            self.status_message = Some(format!("{} is outside the tree", target.display()));
+            return;
+        };
+
+        let mut dir = self.root_path.clone();
+        if let Some(parent) = relative.parent() {
+            for component in parent.components() {
+                dir.push(component);
+                self.expanded.insert(dir.clone());
+            }
+        }
+
+        // Hidden and ignored files on the way are listed without changing
+        // the filters for the rest of the tree
+        self.tree_options.revealed = Some(target.to_path_buf());
+        self.refresh();
+
+        match self.visible_rows.iter().position(|r| r.path == target) {
+            Some(idx) => {
+                self.selected_index = idx;
+                self.update_preview();
+                self.preview_scroll = line.unwrap_or(1).saturating_sub(1);
+            }
+            None => {
+                self.tree_options.revealed = None;
+                self.status_message = Some(format!("Can't show {} in the tree", relative.display()));
             }
         }
     }
@@ -121,7 +822,14 @@ impl App {
             None => return,
         };
 
-        if !row.is_directory {
+        if !row.is_expandable() {
+            if self.link_focus.is_some() {
+                self.follow_link();
+            } else if self.structure().is_some() {
+                self.toggle_node();
+            } else if self.database().is_some() && self.db_table.is_none() {
+                self.open_db_table();
+            }
             return;
         }
 
@@ -140,6 +848,13 @@ impl App {
             None => return,
         };
 
+        // Back from a database table's rows to the schema
+        if self.db_table.take().is_some() {
+            self.preview_scroll = self.db_schema_scroll;
+            self.table_column = 0;
+            return;
+        }
+
         // If on an expanded dir, collapse it
         if row.is_expanded {
             self.expanded.remove(&row.path);
@@ -157,8 +872,21 @@ impl App {
         }
     }
 
+    pub fn toggle_ignore(&mut self) {
+        // Choosing a filter ends any exception made for a revealed path
+        self.tree_options.revealed = None;
+        self.tree_options.respect_ignore = !self.tree_options.respect_ignore;
+        self.status_message = Some(if self.tree_options.respect_ignore {
+            "Hiding ignored files".to_string()
+        } else {
+            "Showing ignored files".to_string()
+        });
+        self.refresh();
+    }
+
     pub fn toggle_hidden(&mut self) {
-        self.show_hidden = !self.show_hidden;
+        self.tree_options.revealed = None;
+        self.tree_options.show_hidden = !self.tree_options.show_hidden;
         self.selected_index = 0;
         self.preview_scroll = 0;
         self.refresh();
@@ -191,10 +919,10 @@ impl App {
                 }
                 // Drop stdin so the process gets EOF
                 drop(child.stdin.take());
-                if let Ok(status) = child.wait() {
-                    if status.success() {
-                        return;
-                    }
+                if let Ok(status) = child.wait()
+                    && status.success()
+                {
+                    return;
                 }
                 // Process failed — try next clipboard command
             }
@@ -209,6 +937,398 @@ impl App {
         self.preview_scroll = self.preview_scroll.saturating_sub(amount);
     }
 
+    /// Show the last page of the preview (of a streamed file, as far as
+    /// it's been indexed)
+    fn scroll_preview_to_end(&mut self, page_height: u16) {
+        // A database table's summary, header and rule stay in place
+        let fixed = if self.db_table.is_some() && self.database().is_some() { 3 } else { 1 };
+        let visible = page_height.saturating_sub(fixed) as usize;
+        self.preview_scroll = self.preview_line_count().saturating_sub(visible);
+    }
+
+    /// The rendered markdown being previewed, if that's what it is
+    pub fn document(&self) -> Option<&Arc<Document>> {
+        match &self.preview_cache.0 {
+            PreviewContent::Markdown(document) => Some(document),
+            _ => None,
+        }
+    }
+
+    /// URL of the selected link
+    pub fn focused_link(&self) -> Option<&str> {
+        let document = self.document()?;
+        Some(document.links.get(self.link_focus?)?.url.as_str())
+    }
+
+    /// The JSON/YAML/TOML tree being previewed, if that's what it is
+    pub fn structure(&self) -> Option<&Arc<Tree>> {
+        match &self.preview_cache.0 {
+            PreviewContent::Structured(tree) => Some(tree),
+            _ => None,
+        }
+    }
+
+    /// The SQLite database being previewed, if that's what it is
+    pub fn database(&self) -> Option<&Arc<Database>> {
+        match &self.preview_cache.0 {
+            PreviewContent::Database(database) => Some(database),
+            _ => None,
+        }
+    }
+
+    /// Select another object of the database schema, scrolling to show as
+    /// much of its schema as fits
+    fn move_db_cursor(&mut self, delta: isize, page_height: u16) {
+        let Some(database) = self.database().cloned() else {
+            return;
+        };
+        let count = database.objects.len();
+        if count == 0 {
+            return;
+        }
+        self.db_cursor = self.db_cursor.saturating_add_signed(delta).min(count - 1);
+
+        let start = database.objects[self.db_cursor].line;
+        let end = database
+            .objects
+            .get(self.db_cursor + 1)
+            .map_or(database.lines.len(), |next| next.line);
+        let visible = (page_height.saturating_sub(1) as usize).max(1);
+        if start < self.preview_scroll {
+            self.preview_scroll = start;
+        } else if end > self.preview_scroll + visible {
+            self.preview_scroll = start.min(end.saturating_sub(visible));
+        }
+    }
+
+    /// Show the rows of the selected table or view
+    fn open_db_table(&mut self) {
+        let Some(object) = self.database().and_then(|d| d.objects.get(self.db_cursor)) else {
+            return;
+        };
+        if !object.has_rows() {
+            self.status_message = Some("Only tables and views have rows".to_string());
+            return;
+        }
+        self.db_table = Some(self.db_cursor);
+        self.db_schema_scroll = self.preview_scroll;
+        self.preview_scroll = 0;
+        self.table_column = 0;
+    }
+
+    /// Read the rows of the open table that fit in `page_height` at the
+    /// scroll position, unless they're what was read last time
+    fn fetch_db_page(&mut self, page_height: u16) {
+        let (Some(database), Some(table)) = (self.database().cloned(), self.db_table) else {
+            self.db_page = None;
+            return;
+        };
+        // Summary, header and rule take three lines
+        let limit = (page_height as usize).saturating_sub(3);
+        let offset = self.preview_scroll.min(self.preview_line_count().saturating_sub(1));
+        if self
+            .db_page
+            .as_ref()
+            .is_some_and(|p| (p.table, p.offset, p.limit) == (table, offset, limit))
+        {
+            return;
+        }
+
+        let rows = self
+            .db_connection(&database.path)
+            .and_then(|connection| sqlite::page(connection, &database.objects[table].name, offset, limit))
+            .map(|page| Table::new(Some(page.columns), page.rows));
+        self.db_page = Some(DbPage {
+            table,
+            offset,
+            limit,
+            rows,
+        });
+    }
+
+    /// A connection to the database at `path`, opened on first use and
+    /// kept for paging through its tables
+    fn db_connection(&mut self, path: &Path) -> Result<&Connection, String> {
+        let connection = match self.db_connection.take() {
+            Some((open, connection)) if open == path => connection,
+            _ => sqlite::open(path)?,
+        };
+        Ok(&self.db_connection.insert((path.to_path_buf(), connection)).1)
+    }
+
+    /// jq path of the selected node
+    pub fn node_path(&self) -> Option<String> {
+        Some(self.structure()?.path(self.node_cursor))
+    }
+
+    /// Move the selected node by `delta` rows, scrolling to keep it in view
+    fn move_node_cursor(&mut self, delta: isize, page_height: u16) {
+        let Some(tree) = self.structure() else {
+            return;
+        };
+        let rows = tree.rows(&self.folded);
+        let current = rows.iter().position(|&i| i == self.node_cursor).unwrap_or(0);
+        let target = current.saturating_add_signed(delta).min(rows.len().saturating_sub(1));
+        self.node_cursor = rows[target];
+
+        let visible = (page_height.saturating_sub(1) as usize).max(1);
+        if target < self.preview_scroll {
+            self.preview_scroll = target;
+        } else if target >= self.preview_scroll + visible {
+            self.preview_scroll = target + 1 - visible;
+        }
+    }
+
+    /// Fold or unfold the selected node. On a value (or an empty object or
+    /// array) its parent is folded instead.
+    fn toggle_node(&mut self) {
+        let Some(tree) = self.structure().cloned() else {
+            return;
+        };
+        let node = self.node_cursor;
+        if tree.has_children(node) {
+            if !self.folded.remove(&node) {
+                self.folded.insert(node);
+            }
+        } else if let Some(parent) = tree.nodes[node].parent {
+            self.folded.insert(parent);
+            self.node_cursor = parent;
+        }
+        self.scroll_to_node_cursor(&tree);
+    }
+
+    fn fold_all(&mut self, fold: bool) {
+        let Some(tree) = self.structure().cloned() else {
+            return;
+        };
+        if fold {
+            self.folded = tree.containers().collect();
+        } else {
+            self.folded.clear();
+        }
+        // Select the outermost fold hiding the selected node
+        let mut ancestor = tree.nodes[self.node_cursor].parent;
+        while let Some(i) = ancestor {
+            if self.folded.contains(&i) {
+                self.node_cursor = i;
+            }
+            ancestor = tree.nodes[i].parent;
+        }
+        self.scroll_to_node_cursor(&tree);
+    }
+
+    /// Folding only moves the selection up, so it's scrolled into view from
+    /// above
+    fn scroll_to_node_cursor(&mut self, tree: &Tree) {
+        let rows = tree.rows(&self.folded);
+        let row = rows.iter().position(|&i| i == self.node_cursor).unwrap_or(0);
+        self.preview_scroll = self.preview_scroll.min(row);
+    }
+
+    /// Headings of the section at the top of the markdown preview, like
+    /// "Usage › Options"
+    pub fn current_section(&self) -> Option<String> {
+        let section = self.document()?.section(self.preview_scroll);
+        let titles: Vec<&str> = section.iter().map(|h| h.text.as_str()).collect();
+        (!titles.is_empty()).then(|| titles.join(" › "))
+    }
+
+    /// Select the next (or previous) link, starting from the first one on
+    /// screen, and scroll it into view
+    fn focus_link(&mut self, forward: bool, page_height: u16) {
+        let Some(document) = self.document().cloned() else {
+            return;
+        };
+        let count = document.links.len();
+        if count == 0 {
+            self.status_message = Some("No links".to_string());
+            return;
+        }
+        let visible = page_height.saturating_sub(1) as usize;
+        let line = |i: usize| document.links[i].regions.first().map_or(0, |r| r.line);
+        let next = match self.link_focus {
+            Some(i) if forward => (i + 1) % count,
+            Some(i) => (i + count - 1) % count,
+            None if forward => (0..count)
+                .find(|&i| line(i) >= self.preview_scroll)
+                .unwrap_or(0),
+            None => (0..count)
+                .rev()
+                .find(|&i| line(i) < self.preview_scroll + visible)
+                .unwrap_or(count - 1),
+        };
+        self.link_focus = Some(next);
+
+        let line = line(next);
+        if line < self.preview_scroll || line >= self.preview_scroll + visible {
+            self.preview_scroll = line.saturating_sub(visible / 2);
+        }
+    }
+
+    /// Go where the selected link points: a heading of this document, or
+    /// another file in the tree (and a heading in it). Other links can only
+    /// be shown.
+    fn follow_link(&mut self) {
+        let Some(url) = self.focused_link().map(str::to_string) else {
+            self.status_message = Some("No link selected".to_string());
+            return;
+        };
+        if let Some(anchor) = url.strip_prefix('#') {
+            self.scroll_to_anchor(anchor);
+            return;
+        }
+        if has_scheme(&url) {
+            self.status_message = Some(format!("Not a file in the tree: {}", url));
+            return;
+        }
+
+        let (path, anchor) = match url.split_once('#') {
+            Some((path, anchor)) => (path, Some(anchor)),
+            None => (url.as_str(), None),
+        };
+        let path = percent_decode(path);
+        let base = match path.strip_prefix('/') {
+            // Absolute links are relative to the root, like on GitHub
+            Some(_) => self.root_path.clone(),
+            None => self
+                .last_preview_path
+                .as_ref()
+                .and_then(|p| p.parent())
+                .map_or_else(|| self.root_path.clone(), Path::to_path_buf),
+        };
+        let target = normalize(&base.join(path.trim_start_matches('/')));
+        if !target.starts_with(&self.root_path) {
+            self.status_message = Some(format!("Outside the tree: {}", url));
+            return;
+        }
+        if self.vfs.metadata(&target).is_err() {
+            self.status_message = Some(format!("No such file: {}", url));
+            return;
+        }
+
+        if Some(&target) == self.last_preview_path.as_ref() {
+            if let Some(anchor) = anchor {
+                self.scroll_to_anchor(anchor);
+            }
+            return;
+        }
+        self.reveal(&target, None);
+        self.pending_anchor = anchor.map(str::to_string);
+    }
+
+    fn scroll_to_anchor(&mut self, anchor: &str) {
+        let line = self
+            .document()
+            .and_then(|document| document.heading(&percent_decode(anchor)))
+            .map(|heading| heading.line);
+        match line {
+            Some(line) => self.preview_scroll = line,
+            None => self.status_message = Some(format!("No heading #{}", anchor)),
+        }
+    }
+
+    /// Start or stop following the previewed file. While following, the
+    /// preview is pinned to the end as lines are appended.
+    fn toggle_follow(&mut self, page_height: u16) {
+        if self.follow.take().is_some() {
+            self.status_message = Some("Stopped following".to_string());
+            return;
+        }
+        let Some(path) = self.last_preview_path.clone() else {
+            return;
+        };
+        match self.vfs.metadata(&path) {
+            Ok(m) if !m.is_dir && !m.is_archive => {
+                self.status_message = Some(format!(
+                    "Following {}",
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                ));
+                self.follow = Some(FollowState {
+                    path,
+                    id: m.id,
+                    size: m.len,
+                    pinned: true,
+                });
+                self.scroll_preview_to_end(page_height);
+            }
+            Ok(_) => self.status_message = Some("Only files can be followed".to_string()),
+            Err(e) => self.status_message = Some(format!("Error: {}", e)),
+        }
+    }
+
+    /// Check the followed file for changes and update the preview. Growth
+    /// adds lines; a file that shrank or was replaced (log rotation) is
+    /// previewed again from the start. The view stays at the end unless it
+    /// was scrolled up.
+    fn poll_follow(&mut self, page_height: u16) {
+        let Some(follow) = &self.follow else {
+            return;
+        };
+        // A rotated file may be missing for a moment; check again later
+        let Ok(m) = self.vfs.metadata(&follow.path) else {
+            return;
+        };
+        if m.id == follow.id && m.len == follow.size {
+            return;
+        }
+
+        let visible = page_height.saturating_sub(1) as usize;
+        let at_end = self.preview_scroll + visible >= self.preview_line_count();
+        let replaced = m.id != follow.id || m.len < follow.size;
+        match &self.preview_cache.0 {
+            PreviewContent::Stream(index) if !replaced => match index.catch_up(self.vfs.as_ref()) {
+                Ok(true) => {}
+                // Still indexing; look again once it's done
+                Ok(false) => return,
+                Err(e) => self.status_message = Some(format!("Error: {}", e)),
+            },
+            _ => self.reload_preview(),
+        }
+
+        if replaced {
+            self.status_message = Some("File was truncated or replaced, reloaded".to_string());
+        }
+        let pinned = at_end || replaced;
+        if let Some(follow) = self.follow.as_mut() {
+            follow.id = m.id;
+            follow.size = m.len;
+            follow.pinned = pinned;
+        }
+        if pinned {
+            self.scroll_preview_to_end(page_height);
+        }
+    }
+
+    /// Total lines of the current preview, which grows while a large file
+    /// is being indexed
+    pub fn preview_line_count(&self) -> usize {
+        match &self.preview_cache.0 {
+            PreviewContent::Stream(index) => index.line_count(),
+            PreviewContent::Structured(tree) => tree.rows(&self.folded).len(),
+            PreviewContent::Database(database) => match self.db_table {
+                Some(i) => match database.objects[i].rows {
+                    Some(rows) => rows as usize,
+                    // Without a row count, paging goes on until a page
+                    // comes back short
+                    None => self.db_page.as_ref().filter(|p| p.table == i).map_or(1, |p| {
+                        let count = p.rows.as_ref().map_or(0, |table| table.rows.len());
+                        p.offset + count + usize::from(count > 0 && count == p.limit)
+                    }),
+                },
+                None => self.preview_cache.1,
+            },
+            _ => self.preview_cache.1,
+        }
+    }
+
+    /// Highlighted lines of a streamed preview, starting at `first`
+    pub fn stream_lines(&self, index: &LineIndex, first: usize, count: usize) -> Result<Vec<Line<'static>>, String> {
+        let lines = index
+            .read_lines(self.vfs.as_ref(), first, count)
+            .map_err(|e| format!("Error: {}", e))?;
+        Ok(self.previewer.highlight_window(index.path(), &lines))
+    }
+
     pub fn click_tree(&mut self, y: u16, area_height: u16) {
         let list_height = area_height.saturating_sub(1) as usize;
         let entries_height = list_height.saturating_sub(2); // minus header + separator
@@ -238,8 +1358,61 @@ impl App {
         self.preview_scroll = 0;
         self.update_preview();
 
-        if self.visible_rows[row_idx].is_directory {
+        if self.visible_rows[row_idx].is_expandable() {
             self.toggle_expand();
         }
     }
 }
+
+/// Whether a link has a scheme (`https:`, `mailto:`), as opposed to being a
+/// path
+fn has_scheme(url: &str) -> bool {
+    url.split_once(':').is_some_and(|(scheme, _)| {
+        // A single letter is a Windows drive
+        scheme.len() > 1
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+/// Decode `%20`-style escapes in a link
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Resolve `.` and `..` without touching the file system (the path may be
+/// inside an archive)
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
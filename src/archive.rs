@@ -0,0 +1,438 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use flate2::read::GzDecoder;
+use liblzma::read::XzDecoder;
+
+use crate::vfs::{DirEntry, Metadata, Vfs};
+
+/// Archive formats that can be listed and expanded in the tree
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+    /// A single gzip-compressed file
+    Gz,
+    /// A single xz-compressed file
+    Xz,
+}
+
+impl ArchiveKind {
+    /// Recognize an archive by its file name
+    pub fn detect(path: &Path) -> Option<ArchiveKind> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        let kind = if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            ArchiveKind::TarGz
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            ArchiveKind::TarXz
+        } else if name.ends_with(".tar") {
+            ArchiveKind::Tar
+        } else if name.ends_with(".gz") {
+            ArchiveKind::Gz
+        } else if name.ends_with(".xz") {
+            ArchiveKind::Xz
+        } else if [".zip", ".jar", ".war", ".apk", ".whl", ".epub", ".xpi"]
+            .iter()
+            .any(|ext| name.ends_with(ext))
+        {
+            ArchiveKind::Zip
+        } else {
+            return None;
+        };
+        Some(kind)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ArchiveKind::Zip => "Zip archive",
+            ArchiveKind::Tar => "tar archive",
+            ArchiveKind::TarGz => "gzip-compressed tar archive",
+            ArchiveKind::TarXz => "xz-compressed tar archive",
+            ArchiveKind::Gz => "gzip-compressed file",
+            ArchiveKind::Xz => "xz-compressed file",
+        }
+    }
+}
+
+/// A file or directory inside an archive
+pub struct Entry {
+    /// `/`-separated path within the archive, without a trailing slash
+    pub path: String,
+    pub is_directory: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl Entry {
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    /// Path of the containing directory, `""` at the top level
+    pub fn parent(&self) -> &str {
+        self.path.rsplit_once('/').map_or("", |(parent, _)| parent)
+    }
+}
+
+/// The most recently listed archive, keyed by path, mtime and size, so
+/// expanding directories and previewing members don't re-read it
+type Listing = (PathBuf, Option<SystemTime>, u64, Arc<Vec<Entry>>);
+static LAST_LISTING: Mutex<Option<Listing>> = Mutex::new(None);
+
+/// Every entry in an archive, sorted by path. Directories that are only
+/// implied by the paths of their contents are included.
+pub fn list(archive: &Path) -> io::Result<Arc<Vec<Entry>>> {
+    let kind = ArchiveKind::detect(archive)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not an archive"))?;
+    let metadata = fs::metadata(archive)?;
+    let modified = metadata.modified().ok();
+
+    // Only hold the lock to look up and store the listing, so a slow archive
+    // doesn't block others
+    if let Some((path, mtime, size, entries)) =
+        LAST_LISTING.lock().unwrap_or_else(|e| e.into_inner()).as_ref()
+        && path == archive
+        && *mtime == modified
+        && *size == metadata.len()
+    {
+        return Ok(entries.clone());
+    }
+
+    let mut entries = match kind {
+        ArchiveKind::Zip => list_zip(archive)?,
+        ArchiveKind::Tar | ArchiveKind::TarGz | ArchiveKind::TarXz => {
+            list_tar(open(archive, kind)?)?
+        }
+        ArchiveKind::Gz => vec![Entry {
+            path: decompressed_name(archive),
+            is_directory: false,
+            size: gzip_size(archive)?,
+            modified,
+        }],
+        ArchiveKind::Xz => {
+            let size = io::copy(&mut open(archive, kind)?, &mut io::sink())?;
+            vec![Entry {
+                path: decompressed_name(archive),
+                is_directory: false,
+                size,
+                modified,
+            }]
+        }
+    };
+    add_implied_directories(&mut entries);
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let entries = Arc::new(entries);
+    *LAST_LISTING.lock().unwrap_or_else(|e| e.into_inner()) = Some((
+        archive.to_path_buf(),
+        modified,
+        metadata.len(),
+        entries.clone(),
+    ));
+    Ok(entries)
+}
+
+/// The uncompressed size from the gzip trailer (ISIZE), which is only
+/// correct modulo 4 GiB but saves decompressing the whole file
+fn gzip_size(archive: &Path) -> io::Result<u64> {
+    let mut file = File::open(archive)?;
+    file.seek(SeekFrom::End(-4))?;
+    let mut trailer = [0; 4];
+    file.read_exact(&mut trailer)?;
+    Ok(u32::from_le_bytes(trailer) as u64)
+}
+
+/// Serves the contents of archives through the `Vfs` interface. Paths are
+/// either an archive file itself (its top level) or a path inside one.
+#[derive(Default)]
+pub struct ArchiveVfs;
+
+impl ArchiveVfs {
+    fn locate(path: &Path) -> io::Result<(PathBuf, String)> {
+        if ArchiveKind::detect(path).is_some() && path.is_file() {
+            return Ok((path.to_path_buf(), String::new()));
+        }
+        split_path(path).ok_or_else(not_found)
+    }
+}
+
+impl Vfs for ArchiveVfs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let (archive, dir) = Self::locate(path)?;
+        let entries = list(&archive)?;
+        Ok(entries
+            .iter()
+            .filter(|entry| entry.parent() == dir)
+            .map(|entry| DirEntry {
+                name: entry.name().to_string(),
+                path: archive.join(&entry.path),
+                is_dir: entry.is_directory,
+                is_symlink: false,
+            })
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let (archive, member) = Self::locate(path)?;
+        if member.is_empty() {
+            let m = fs::metadata(&archive)?;
+            return Ok(Metadata {
+                is_archive: true,
+                len: m.len(),
+                modified: m.modified().ok(),
+                ..Metadata::default()
+            });
+        }
+        let entries = list(&archive)?;
+        let entry = entries
+            .iter()
+            .find(|entry| entry.path == member)
+            .ok_or_else(not_found)?;
+        Ok(Metadata {
+            is_dir: entry.is_directory,
+            is_archive: false,
+            len: entry.size,
+            modified: entry.modified,
+            id: None,
+        })
+    }
+
+    fn read_range(&self, path: &Path, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        let (archive, member) = Self::locate(path)?;
+        read_member(&archive, &member, offset, len)
+    }
+
+    fn read_link(&self, _path: &Path) -> io::Result<PathBuf> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "archive members are never symlinks",
+        ))
+    }
+}
+
+/// Read up to `len` bytes of a member, starting at `offset`, without
+/// extracting the archive. Compressed data is decoded from the start.
+fn read_member(archive: &Path, member: &str, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    let kind = ArchiveKind::detect(archive)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not an archive"))?;
+    let mut buf = Vec::new();
+
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipArchive::new(BufReader::new(File::open(archive)?))
+                .map_err(io::Error::other)?;
+            let index = (0..zip.len())
+                .find(|&i| zip.name_for_index(i).and_then(normalize).as_deref() == Some(member))
+                .ok_or_else(not_found)?;
+            let mut file = zip.by_index(index).map_err(io::Error::other)?;
+            skip(&mut file, offset)?;
+            file.take(len).read_to_end(&mut buf)?;
+        }
+        ArchiveKind::Tar | ArchiveKind::TarGz | ArchiveKind::TarXz => {
+            let mut tar = tar::Archive::new(open(archive, kind)?);
+            let mut entry = tar
+                .entries()?
+                .flatten()
+                .find(|entry| {
+                    entry
+                        .path()
+                        .ok()
+                        .and_then(|p| normalize(&p.to_string_lossy()))
+                        .as_deref()
+                        == Some(member)
+                })
+                .ok_or_else(not_found)?;
+            skip(&mut entry, offset)?;
+            entry.take(len).read_to_end(&mut buf)?;
+        }
+        ArchiveKind::Gz | ArchiveKind::Xz => {
+            if member != decompressed_name(archive) {
+                return Err(not_found());
+            }
+            let mut reader = open(archive, kind)?;
+            skip(&mut reader, offset)?;
+            reader.take(len).read_to_end(&mut buf)?;
+        }
+    }
+    Ok(buf)
+}
+
+/// Split a path like `/tmp/src.zip/lib/main.rs` into the archive file and
+/// the member path within it. Returns `None` for ordinary paths.
+pub fn split_path(path: &Path) -> Option<(PathBuf, String)> {
+    // Paths that exist on disk aren't inside an archive
+    if fs::symlink_metadata(path).is_ok() {
+        return None;
+    }
+    let archive = path
+        .ancestors()
+        .skip(1)
+        .find(|dir| ArchiveKind::detect(dir).is_some() && dir.is_file())?;
+    let member = path.strip_prefix(archive).ok()?;
+    let member: Vec<String> = member
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    Some((archive.to_path_buf(), member.join("/")))
+}
+
+/// `2024-05-01 12:30` in UTC
+pub fn format_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let minutes = secs.rem_euclid(86400) / 60;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+/// A reader for the decompressed contents of an archive (for zip, the
+/// file itself)
+fn open(archive: &Path, kind: ArchiveKind) -> io::Result<Box<dyn Read>> {
+    let file = BufReader::new(File::open(archive)?);
+    Ok(match kind {
+        ArchiveKind::TarGz | ArchiveKind::Gz => Box::new(GzDecoder::new(file)),
+        ArchiveKind::TarXz | ArchiveKind::Xz => Box::new(XzDecoder::new(file)),
+        ArchiveKind::Zip | ArchiveKind::Tar => Box::new(file),
+    })
+}
+
+fn list_zip(archive: &Path) -> io::Result<Vec<Entry>> {
+    let mut zip =
+        zip::ZipArchive::new(BufReader::new(File::open(archive)?)).map_err(io::Error::other)?;
+    let mut entries = Vec::with_capacity(zip.len());
+    for i in 0..zip.len() {
+        let file = zip.by_index_raw(i).map_err(io::Error::other)?;
+        let Some(path) = normalize(file.name()) else {
+            continue;
+        };
+        let modified = file.last_modified().and_then(|t| {
+            let days = days_from_civil(t.year() as i64, t.month() as i64, t.day() as i64);
+            let secs = days * 86400
+                + t.hour() as i64 * 3600
+                + t.minute() as i64 * 60
+                + t.second() as i64;
+            Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+        });
+        entries.push(Entry {
+            path,
+            is_directory: file.is_dir(),
+            size: file.size(),
+            modified,
+        });
+    }
+    Ok(entries)
+}
+
+fn list_tar(reader: Box<dyn Read>) -> io::Result<Vec<Entry>> {
+    let mut tar = tar::Archive::new(reader);
+    let mut entries = Vec::new();
+    for entry in tar.entries()? {
+        let entry = entry?;
+        let Some(path) = entry.path().ok().and_then(|p| normalize(&p.to_string_lossy())) else {
+            continue;
+        };
+        let header = entry.header();
+        entries.push(Entry {
+            path,
+            is_directory: header.entry_type().is_dir(),
+            size: header.size().unwrap_or(0),
+            modified: header
+                .mtime()
+                .ok()
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+        });
+    }
+    Ok(entries)
+}
+
+/// Add entries for directories that contain files but have no entry of
+/// their own (common in zip files)
+fn add_implied_directories(entries: &mut Vec<Entry>) {
+    let mut known: HashSet<String> = entries.iter().map(|e| e.path.clone()).collect();
+    let mut implied = Vec::new();
+    for entry in entries.iter() {
+        let mut dir = entry.parent();
+        while !dir.is_empty() && known.insert(dir.to_string()) {
+            implied.push(Entry {
+                path: dir.to_string(),
+                is_directory: true,
+                size: 0,
+                modified: None,
+            });
+            dir = dir.rsplit_once('/').map_or("", |(parent, _)| parent);
+        }
+    }
+    entries.extend(implied);
+}
+
+/// Clean up a member path: drop `./` and leading or trailing slashes, and
+/// reject paths that would escape the archive
+fn normalize(path: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            Component::CurDir | Component::RootDir => {}
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// Name of the single file in a `.gz` or `.xz` file: its name without the
+/// compression extension
+fn decompressed_name(archive: &Path) -> String {
+    archive
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn skip(reader: &mut impl Read, bytes: u64) -> io::Result<()> {
+    io::copy(&mut reader.take(bytes), &mut io::sink())?;
+    Ok(())
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "no such file in archive")
+}
+
+// Conversions between days since 1970-01-01 and (year, month, day), from
+// Howard Hinnant's date algorithms
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
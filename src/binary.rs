@@ -0,0 +1,220 @@
+use std::io;
+use std::path::Path;
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+
+use crate::vfs::Vfs;
+
+/// How much of a file is inspected to decide whether it's text
+pub const SNIFF_BYTES: usize = 8 * 1024;
+
+/// Share of invalid UTF-8 bytes above which a file is treated as binary
+const MAX_INVALID_UTF8_RATIO: f64 = 0.1;
+
+pub const BYTES_PER_ROW: usize = 16;
+
+// Styles for the hex dump, by byte class (like hexyl)
+const OFFSET: Style = Style::new().add_modifier(Modifier::DIM);
+const NULL: Style = Style::new().fg(Color::DarkGray);
+const PRINTABLE: Style = Style::new().fg(Color::Cyan);
+const WHITESPACE: Style = Style::new().fg(Color::Green);
+const OTHER: Style = Style::new().fg(Color::Yellow);
+
+/// Inspect the start of a file. Returns a description of the binary format
+/// if the content isn't text, or `None` for text.
+pub fn sniff_bytes(buf: &[u8]) -> Option<&'static str> {
+    let text = !buf.contains(&0) && invalid_utf8_bytes(buf) as f64 <= buf.len() as f64 * MAX_INVALID_UTF8_RATIO;
+    // A magic made of printable characters could just be how a text file
+    // starts, so it only counts if the rest doesn't read as text
+    if let Some((kind, printable)) = magic_kind(buf)
+        && !(printable && text)
+    {
+        return Some(kind);
+    }
+    if text { None } else { Some("data") }
+}
+
+/// Recognize common binary formats by their leading magic bytes, and say
+/// whether the bytes that matched are all printable ASCII
+fn magic_kind(buf: &[u8]) -> Option<(&'static str, bool)> {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"\x7fELF", "ELF"),
+        (b"\x89PNG\r\n\x1a\n", "PNG image"),
+        (b"\xff\xd8\xff", "JPEG image"),
+        (b"GIF87a", "GIF image"),
+        (b"GIF89a", "GIF image"),
+        (b"\x00\x00\x01\x00", "ICO image"),
+        (b"%PDF-", "PDF document"),
+        (b"PK\x03\x04", "Zip archive"),
+        (b"PK\x05\x06", "Zip archive"),
+        (b"\x1f\x8b", "gzip"),
+        (b"\xfd7zXZ\x00", "xz"),
+        (b"\x28\xb5\x2f\xfd", "zstd"),
+        (b"7z\xbc\xaf\x27\x1c", "7-Zip archive"),
+        (b"Rar!\x1a\x07", "RAR archive"),
+        (b"!<arch>\n", "ar archive"),
+        (b"SQLite format 3\x00", "SQLite database"),
+        (b"\x00asm", "WebAssembly"),
+        (b"\xcf\xfa\xed\xfe", "Mach-O"),
+        (b"\xce\xfa\xed\xfe", "Mach-O"),
+        (b"\xca\xfe\xba\xbe", "Mach-O universal or Java class"),
+        (b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1", "MS Office document"),
+        (b"OggS", "Ogg media"),
+        (b"fLaC", "FLAC audio"),
+        (b"ID3", "MP3 audio"),
+        (b"wOFF", "WOFF font"),
+        (b"wOF2", "WOFF2 font"),
+        (b"\x00\x01\x00\x00\x00", "TrueType font"),
+        (b"OTTO", "OpenType font"),
+        (b"DIRC", "Git index"),
+    ];
+
+    if let Some((magic, kind)) = MAGIC.iter().find(|(magic, _)| buf.starts_with(magic)) {
+        let printable = magic.iter().all(|&b| b.is_ascii_graphic() || b == b' ');
+        return Some((kind, printable));
+    }
+
+    weak_magic_kind(buf).map(|kind| (kind, true))
+}
+
+/// Formats with a magic number past the first bytes, or a weak one that
+/// needs more checks to avoid matching text. Their magics are printable, so
+/// they're only trusted in files that don't read as text.
+fn weak_magic_kind(buf: &[u8]) -> Option<&'static str> {
+    if buf.len() >= 12 && buf.starts_with(b"RIFF") {
+        return match &buf[8..12] {
+            b"WEBP" => Some("WebP image"),
+            b"WAVE" => Some("WAV audio"),
+            b"AVI " => Some("AVI video"),
+            _ => Some("RIFF data"),
+        };
+    }
+    if buf.len() >= 12 && &buf[4..8] == b"ftyp" {
+        return match &buf[8..12] {
+            b"avif" | b"avis" => Some("AVIF image"),
+            b"heic" | b"heix" | b"mif1" => Some("HEIF image"),
+            b"qt  " => Some("QuickTime video"),
+            _ => Some("MP4 media"),
+        };
+    }
+    if buf.len() >= 14 && buf.starts_with(b"BM") && buf[6..10] == [0, 0, 0, 0] {
+        return Some("BMP image");
+    }
+    if buf.len() >= 64 && buf.starts_with(b"MZ") {
+        return Some("PE executable");
+    }
+    if buf.len() >= 4 && buf.starts_with(b"BZh") && buf[3].is_ascii_digit() {
+        return Some("bzip2");
+    }
+    if buf.len() >= 262 && &buf[257..262] == b"ustar" {
+        return Some("tar archive");
+    }
+    None
+}
+
+/// Count bytes that aren't part of valid UTF-8, ignoring a multi-byte
+/// character cut off at the end of the buffer
+fn invalid_utf8_bytes(mut buf: &[u8]) -> usize {
+    let mut invalid = 0;
+    loop {
+        match std::str::from_utf8(buf) {
+            Ok(_) => return invalid,
+            Err(e) => match e.error_len() {
+                Some(len) => {
+                    invalid += len;
+                    buf = &buf[e.valid_up_to() + len..];
+                }
+                None => return invalid,
+            },
+        }
+    }
+}
+
+/// Number of rows in the hex dump of a file of `size` bytes
+pub fn hex_rows(size: u64) -> usize {
+    (size as usize).div_ceil(BYTES_PER_ROW)
+}
+
+/// Render `rows` rows of an `xxd`-style dump starting at row `first_row`,
+/// reading only that part of the file
+pub fn hex_dump(vfs: &dyn Vfs, path: &Path, first_row: usize, rows: usize) -> io::Result<Vec<Line<'static>>> {
+    let offset = (first_row * BYTES_PER_ROW) as u64;
+    let buf = vfs.read_range(path, offset, (rows * BYTES_PER_ROW) as u64)?;
+    Ok(hex_lines(&buf, offset))
+}
+
+/// Dump bytes already in memory, labelling the first row with `offset`
+pub fn hex_lines(bytes: &[u8], offset: u64) -> Vec<Line<'static>> {
+    bytes
+        .chunks(BYTES_PER_ROW)
+        .enumerate()
+        .map(|(i, chunk)| hex_row(offset + (i * BYTES_PER_ROW) as u64, chunk))
+        .collect()
+}
+
+/// `00000010: 0200 3e00 0100 0000 1054 0000 0000 0000  ..>......T......`
+fn hex_row(offset: u64, bytes: &[u8]) -> Line<'static> {
+    let mut hex = vec![Span::styled(format!("{:08x}:", offset), OFFSET), Span::raw(" ")];
+    let mut ascii = Vec::new();
+
+    for i in 0..BYTES_PER_ROW {
+        match bytes.get(i) {
+            Some(&b) => {
+                let style = byte_style(b);
+                hex.push(Span::styled(format!("{:02x}", b), style));
+                let c = if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' };
+                ascii.push(Span::styled(c.to_string(), style));
+            }
+            None => hex.push(Span::raw("  ")),
+        }
+        if i % 2 == 1 {
+            hex.push(Span::raw(" "));
+        }
+    }
+
+    hex.push(Span::raw(" "));
+    hex.extend(ascii);
+    Line::from(hex)
+}
+
+fn byte_style(b: u8) -> Style {
+    match b {
+        0 => NULL,
+        _ if b.is_ascii_graphic() => PRINTABLE,
+        _ if b.is_ascii_whitespace() => WHITESPACE,
+        _ => OTHER,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_starting_with_a_printable_magic_is_text() {
+        for start in ["ID3 tags", "OTTO", "DIRC", "OggS", "fLaC", "wOFF", "%PDF-", "GIF89a", "BZh9"] {
+            let text = format!("{} are described below.\nSecond line\n", start);
+            assert_eq!(sniff_bytes(text.as_bytes()), None, "{:?}", start);
+        }
+        let dos = format!("MZ is a prefix\n{}", "text ".repeat(20));
+        assert_eq!(sniff_bytes(dos.as_bytes()), None);
+    }
+
+    #[test]
+    fn binary_headers_are_recognized() {
+        assert_eq!(sniff_bytes(b"ID3\x03\x00\x00\x00\x00\x1f\x76TIT2"), Some("MP3 audio"));
+        assert_eq!(sniff_bytes(b"GIF89a\x01\x00\x01\x00\x80\x00\x00"), Some("GIF image"));
+        assert_eq!(sniff_bytes(b"\x7fELF\x02\x01\x01\x00"), Some("ELF"));
+        assert_eq!(sniff_bytes(b"\x89PNG\r\n\x1a\n"), Some("PNG image"));
+        let mut exe = b"MZ\x90\x00\x03\x00".to_vec();
+        exe.resize(128, 0);
+        assert_eq!(sniff_bytes(&exe), Some("PE executable"));
+    }
+
+    #[test]
+    fn unknown_binary_is_data() {
+        assert_eq!(sniff_bytes(b"plain\x00text"), Some("data"));
+        assert_eq!(sniff_bytes("plain text, ünïcode".as_bytes()), None);
+    }
+}
@@ -0,0 +1,222 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use lru::LruCache;
+use ratatui::text::{Line, Span};
+
+use crate::preview::PreviewContent;
+
+/// Everything a rendered preview depends on. A change to any of them means
+/// the preview has to be generated again.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    pub size: u64,
+    pub width: u16,
+    pub theme: String,
+    /// Size above which text is streamed instead of read in full
+    pub max_bytes: u64,
+}
+
+/// Recently rendered previews, so moving back to a file shows it without
+/// reading and highlighting it again. The least recently used previews are
+/// dropped once their total size exceeds the limit.
+pub struct PreviewCache {
+    /// Each preview with its line count and its weight when last measured
+    entries: LruCache<CacheKey, (PreviewContent, usize, usize)>,
+    bytes: usize,
+    max_bytes: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl PreviewCache {
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            entries: LruCache::unbounded(),
+            bytes: 0,
+            max_bytes: max_bytes as usize,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn set_max_bytes(&mut self, max_bytes: u64) {
+        self.max_bytes = max_bytes as usize;
+        self.evict();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    pub fn get(&mut self, key: &CacheKey) -> Option<(PreviewContent, usize)> {
+        match self.entries.get(key) {
+            Some((content, lines, _)) => {
+                self.hits += 1;
+                Some((content.clone(), *lines))
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: CacheKey, preview: &(PreviewContent, usize)) {
+        let size = match &preview.0 {
+            PreviewContent::Loading | PreviewContent::Error(_) => return,
+            content => weight(content),
+        };
+
+        // Previews of an older version of the file can't be shown again
+        let stale: Vec<CacheKey> = self
+            .entries
+            .iter()
+            .map(|(k, _)| k)
+            .filter(|k| k.path == key.path && (k.modified != key.modified || k.size != key.size))
+            .cloned()
+            .collect();
+        for k in stale {
+            self.remove(&k);
+        }
+
+        if size > self.max_bytes {
+            return;
+        }
+        self.remove(&key);
+        self.bytes += size;
+        self.entries.put(key, (preview.0.clone(), preview.1, size));
+        self.reweigh();
+        self.evict();
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some((_, _, size)) = self.entries.pop(key) {
+            self.bytes -= size;
+        }
+    }
+
+    /// Measure the entries again: streams keep indexing and code keeps
+    /// highlighting after they're cached
+    fn reweigh(&mut self) {
+        for (_, (content, _, size)) in self.entries.iter_mut() {
+            let now = weight(content);
+            self.bytes = self.bytes - *size + now;
+            *size = now;
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.bytes > self.max_bytes {
+            match self.entries.pop_lru() {
+                Some((_, (_, _, size))) => self.bytes -= size,
+                None => break,
+            }
+        }
+    }
+
+    /// One-line summary for the debug view
+    pub fn stats(&self) -> String {
+        let lookups = self.hits + self.misses;
+        let hit_rate = (self.hits * 100).checked_div(lookups).unwrap_or(0);
+        format!(
+            "cache: {} previews, {} of {}, {} hits, {} misses ({}%)",
+            self.entries.len(),
+            crate::preview::format_size(self.bytes as u64),
+            crate::preview::format_size(self.max_bytes as u64),
+            self.hits,
+            self.misses,
+            hit_rate
+        )
+    }
+}
+
+/// Approximate memory held by a preview. Streams and hex dumps of local
+/// files read the file as they're scrolled, so only what they keep about it
+/// counts.
+fn weight(content: &PreviewContent) -> usize {
+    match content {
+        PreviewContent::Text(lines) => lines_weight(lines),
+        PreviewContent::Markdown(document) => lines_weight(&document.lines),
+        PreviewContent::Structured(tree) => tree.weight(),
+        PreviewContent::Table(table) => table.weight(),
+        PreviewContent::Database(database) => lines_weight(&database.lines),
+        PreviewContent::Elf(info) => lines_weight(&info.lines),
+        PreviewContent::Directory(s) | PreviewContent::Error(s) => s.len(),
+        PreviewContent::Code(code) => code.weight(),
+        PreviewContent::Hex { header, bytes, .. } => header.len() + bytes.as_ref().map_or(0, |b| b.len()),
+        PreviewContent::Image { header, image, .. } => header.len() + image.as_raw().len(),
+        PreviewContent::Stream(index) => index.weight(),
+        PreviewContent::Loading | PreviewContent::Empty => 0,
+    }
+}
+
+fn lines_weight(lines: &[Line]) -> usize {
+    lines
+        .iter()
+        .flat_map(|line| &line.spans)
+        .map(|span| span.content.len() + std::mem::size_of::<Span>())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use syntect::highlighting::ThemeSet;
+    use syntect::parsing::SyntaxSet;
+
+    use super::*;
+    use crate::highlight::Highlighted;
+
+    fn key(path: &str) -> CacheKey {
+        CacheKey {
+            path: PathBuf::from(path),
+            modified: None,
+            size: 0,
+            width: 80,
+            theme: "theme".to_string(),
+            max_bytes: 1024,
+        }
+    }
+
+    fn text(len: usize) -> (PreviewContent, usize) {
+        (PreviewContent::Text(vec![Line::from("x".repeat(len))]), 1)
+    }
+
+    #[test]
+    fn size_limit_is_part_of_the_key() {
+        let mut cache = PreviewCache::new(1 << 20);
+        cache.insert(key("/a"), &text(10));
+        assert!(cache.get(&key("/a")).is_some());
+        let raised = CacheKey {
+            max_bytes: 4096,
+            ..key("/a")
+        };
+        assert!(cache.get(&raised).is_none());
+    }
+
+    #[test]
+    fn entries_that_grow_after_insertion_are_evicted() {
+        let syntax_set = Arc::new(SyntaxSet::load_defaults_newlines());
+        let syntax = syntax_set.find_syntax_by_extension("rs").unwrap().clone();
+        let theme = &ThemeSet::load_defaults().themes["base16-ocean.dark"];
+        let source = "fn main() { let x = 1; }\n".repeat(200);
+        let code = Arc::new(Highlighted::new(source, &syntax, syntax_set.clone(), theme));
+
+        let before = code.weight();
+        let mut cache = PreviewCache::new((before * 2) as u64);
+        cache.insert(key("/code.rs"), &(PreviewContent::Code(code.clone()), 200));
+        assert!(cache.get(&key("/code.rs")).is_some());
+
+        // Highlighting the whole file makes the cached entry heavier
+        code.lines(0, 200);
+        assert!(code.weight() > before * 2);
+        cache.insert(key("/b"), &text(10));
+        assert!(cache.get(&key("/code.rs")).is_none());
+        assert!(cache.get(&key("/b")).is_some());
+    }
+}
@@ -0,0 +1,235 @@
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::tree::SortMode;
+
+pub const USAGE: &str = "\
+A fast terminal file browser with tree navigation and syntax-highlighted preview
+
+Usage: browse [OPTIONS] [+LINE] [PATH]
+
+Arguments:
+  [PATH]   Directory to browse, or a file to reveal [default: .]
+  [+LINE]  Scroll the preview of a revealed file to LINE
+
+Options:
+  -a, --hidden           Show hidden files
+      --ignore           Hide files matched by .gitignore
+      --no-ignore        Show files matched by .gitignore
+  -s, --sort MODE        Sort by name, size, modified or extension [default: name]
+  -t, --theme NAME       Syntax highlighting theme
+      --no-preview       Hide the preview pane
+      --split PERCENT    Width of the tree pane, 10-90 [default: 35]
+  -d, --depth N          Expand directories N levels deep on startup
+  -c, --config PATH      Read configuration from PATH
+                         [default: $XDG_CONFIG_HOME/browse/config.toml]
+      --list-keys        Print the effective key bindings and exit
+  -h, --help             Print help
+  -V, --version          Print version
+";
+
+/// Options given on the command line. `None` means "not specified".
+#[derive(Default)]
+pub struct Args {
+    pub path: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub hidden: bool,
+    pub respect_ignore: Option<bool>,
+    pub sort: Option<SortMode>,
+    pub theme: Option<String>,
+    pub no_preview: bool,
+    pub split: Option<u16>,
+    pub depth: Option<usize>,
+    pub config: Option<PathBuf>,
+    pub list_keys: bool,
+}
+
+impl Args {
+    /// Override config file settings with those given on the command line
+    pub fn apply_to(&self, config: &mut Config) {
+        if self.hidden {
+            config.general.show_hidden = true;
+        }
+        if let Some(respect_ignore) = self.respect_ignore {
+            config.general.respect_ignore = respect_ignore;
+        }
+        if let Some(sort) = self.sort {
+            config.general.sort = sort;
+        }
+        if let Some(theme) = &self.theme {
+            config.preview.theme = theme.clone();
+        }
+        if self.no_preview {
+            config.layout.show_preview = false;
+        }
+        if let Some(split) = self.split {
+            config.layout.split = split;
+        }
+    }
+}
+
+pub enum Command {
+    Run(Args),
+    Help,
+    Version,
+}
+
+/// Parse arguments (excluding the program name)
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+    let mut only_positional = false;
+
+    while let Some(arg) = args.next() {
+        if only_positional || !arg.starts_with('-') || arg == "-" {
+            positional(&mut parsed, arg, only_positional)?;
+            continue;
+        }
+        if arg == "--" {
+            only_positional = true;
+            continue;
+        }
+
+        // Support both `--opt value` and `--opt=value`
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |what: &str| -> Result<String, String> {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("option '{}' requires {}", name, what))
+        };
+        let no_value = || match &inline_value {
+            Some(value) => Err(format!("option '{}' doesn't take a value (got '{}')", name, value)),
+            None => Ok(()),
+        };
+
+        match name.as_str() {
+            "-h" | "--help" => {
+                no_value()?;
+                return Ok(Command::Help);
+            }
+            "-V" | "--version" => {
+                no_value()?;
+                return Ok(Command::Version);
+            }
+            "-a" | "--hidden" => {
+                no_value()?;
+                parsed.hidden = true;
+            }
+            "--ignore" => {
+                no_value()?;
+                parsed.respect_ignore = Some(true);
+            }
+            "--no-ignore" => {
+                no_value()?;
+                parsed.respect_ignore = Some(false);
+            }
+            "--no-preview" => {
+                no_value()?;
+                parsed.no_preview = true;
+            }
+            "--list-keys" => {
+                no_value()?;
+                parsed.list_keys = true;
+            }
+            "-s" | "--sort" => {
+                let mode = value("a sort mode")?;
+                parsed.sort = Some(SortMode::parse(&mode).ok_or_else(|| {
+                    format!(
+                        "invalid sort mode '{}' (expected one of: {})",
+                        mode,
+                        SortMode::ALL.map(SortMode::name).join(", ")
+                    )
+                })?);
+            }
+            "-t" | "--theme" => parsed.theme = Some(value("a theme name")?),
+            "--split" => {
+                let percent = value("a percentage")?;
+                match percent.trim_end_matches('%').parse::<u16>() {
+                    Ok(n) if (10..=90).contains(&n) => parsed.split = Some(n),
+                    _ => {
+                        return Err(format!(
+                            "invalid split '{}' (expected a percentage from 10 to 90)",
+                            percent
+                        ));
+                    }
+                }
+            }
+            "-d" | "--depth" => {
+                let depth = value("a number")?;
+                parsed.depth = Some(
+                    depth
+                        .parse()
+                        .map_err(|_| format!("invalid depth '{}' (expected a number)", depth))?,
+                );
+            }
+            "-c" | "--config" => parsed.config = Some(PathBuf::from(value("a path")?)),
+            _ => return Err(format!("unknown option '{}'", name)),
+        }
+    }
+
+    Ok(Command::Run(parsed))
+}
+
+/// Handle PATH and `+LINE` (like vim's `vim +42 file`), in either order.
+/// After `--` every argument is a path, even one starting with `+`.
+fn positional(parsed: &mut Args, arg: String, literal: bool) -> Result<(), String> {
+    if !literal && let Some(line) = arg.strip_prefix('+') {
+        let line = line
+            .parse()
+            .map_err(|_| format!("invalid line number '{}'", arg))?;
+        parsed.line = Some(line);
+        return Ok(());
+    }
+    if parsed.path.is_some() {
+        return Err(format!("unexpected argument '{}' (only one path is accepted)", arg));
+    }
+    parsed.path = Some(PathBuf::from(arg));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str]) -> Result<Args, String> {
+        match parse(args.iter().map(|s| s.to_string()))? {
+            Command::Run(args) => Ok(args),
+            _ => Err("not a run command".to_string()),
+        }
+    }
+
+    #[test]
+    fn parses_options_in_both_forms() {
+        let args = run(&["-a", "--sort=size", "-d", "2", "+42", "src/main.rs"]).unwrap();
+        assert!(args.hidden);
+        assert_eq!(args.sort, Some(SortMode::Size));
+        assert_eq!(args.depth, Some(2));
+        assert_eq!(args.line, Some(42));
+        assert_eq!(args.path, Some(PathBuf::from("src/main.rs")));
+        assert!(run(&["--split", "95"]).is_err());
+        assert!(run(&["--depth"]).is_err());
+        assert!(run(&["a", "b"]).is_err());
+    }
+
+    #[test]
+    fn flags_dont_take_values() {
+        let err = run(&["--hidden=x"]).err().unwrap();
+        assert_eq!(err, "option '--hidden' doesn't take a value (got 'x')");
+        assert!(run(&["--no-preview=false"]).is_err());
+        assert!(parse(["--help=1".to_string()]).is_err());
+    }
+
+    #[test]
+    fn arguments_after_double_dash_are_paths() {
+        let err = run(&["--", "+notes", "-a"]).err().unwrap();
+        assert!(err.contains("only one path"), "{}", err);
+        let args = run(&["--", "+42"]).unwrap();
+        assert_eq!(args.path, Some(PathBuf::from("+42")));
+        assert_eq!(args.line, None);
+        assert!(!args.hidden);
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer};
+
+use crate::graphics::ImageProtocol;
+use crate::keymap::{KeyList, Keymap};
+use crate::theme;
+use crate::tree::{SortMode, TreeOptions};
+
+/// Settings loaded from `config.toml`. Every key is optional; unknown keys
+/// are rejected so typos don't go unnoticed.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub general: GeneralConfig,
+    pub layout: LayoutConfig,
+    pub preview: PreviewConfig,
+    pub colors: ColorConfig,
+    pub syntax: SyntaxConfig,
+    /// Action name to key sequences, replacing that action's defaults
+    pub keys: BTreeMap<String, KeyList>,
+    /// Built from the defaults and `keys` when the config is loaded
+    #[serde(skip)]
+    pub keymap: Keymap,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeneralConfig {
+    pub show_hidden: bool,
+    pub respect_ignore: bool,
+    pub sort: SortMode,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    pub show_preview: bool,
+    /// Width of the tree pane as a percentage of the terminal
+    pub split: u16,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PreviewConfig {
+    /// Syntax theme name, or "auto" for the theme last picked with `T`
+    /// (falling back to `light_theme`/`dark_theme` by terminal background)
+    pub theme: String,
+    pub light_theme: String,
+    pub dark_theme: String,
+    /// Files larger than this are not read in full
+    pub max_bytes: u64,
+    /// Lines scrolled by `J`/`K`
+    pub scroll_lines: usize,
+    /// Lines scrolled per mouse wheel step
+    pub mouse_scroll_lines: usize,
+    /// How images are drawn: auto, kitty, iterm2, sixel or halfblocks
+    pub image_protocol: ImageProtocol,
+    /// Memory for recently viewed previews; 0 disables the cache
+    pub cache_bytes: u64,
+    /// Make links in markdown previews clickable in terminals that support
+    /// hyperlinks (OSC 8)
+    pub hyperlinks: bool,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorConfig {
+    #[serde(deserialize_with = "color")]
+    pub header: Color,
+    #[serde(deserialize_with = "color")]
+    pub directory: Color,
+    #[serde(deserialize_with = "color")]
+    pub selected: Color,
+    #[serde(deserialize_with = "color")]
+    pub status: Color,
+    #[serde(deserialize_with = "color")]
+    pub border: Color,
+}
+
+/// Which syntax highlights which files, checked before the syntaxes' own
+/// file types. Entries are added to the built-in ones, replacing any for
+/// the same extension or name.
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyntaxConfig {
+    /// Extension (without the dot, also matched against code block
+    /// languages in markdown) to syntax name
+    #[serde(deserialize_with = "with_default_extensions")]
+    pub extensions: BTreeMap<String, String>,
+    /// Exact file name to syntax name
+    #[serde(deserialize_with = "with_default_filenames")]
+    pub filenames: BTreeMap<String, String>,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            show_preview: true,
+            split: 35,
+        }
+    }
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        Self {
+            theme: theme::AUTO.to_string(),
+            light_theme: theme::DEFAULT_LIGHT.to_string(),
+            dark_theme: theme::DEFAULT_DARK.to_string(),
+            max_bytes: 512 * 1024,
+            scroll_lines: 1,
+            mouse_scroll_lines: 3,
+            image_protocol: ImageProtocol::Auto,
+            cache_bytes: 32 * 1024 * 1024,
+            hyperlinks: false,
+        }
+    }
+}
+
+const DEFAULT_EXTENSIONS: &[(&str, &str)] = &[
+    ("cjs", "JavaScript"),
+    ("jsonc", "JSON"),
+    ("jsx", "TypeScriptReact"),
+    ("mjs", "JavaScript"),
+];
+
+const DEFAULT_FILENAMES: &[(&str, &str)] = &[
+    (".clang-format", "YAML"),
+    ("Containerfile", "Dockerfile"),
+];
+
+fn mapping(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+    entries
+        .iter()
+        .map(|(from, to)| (from.to_string(), to.to_string()))
+        .collect()
+}
+
+impl Default for SyntaxConfig {
+    fn default() -> Self {
+        Self {
+            extensions: mapping(DEFAULT_EXTENSIONS),
+            filenames: mapping(DEFAULT_FILENAMES),
+        }
+    }
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        Self {
+            header: Color::Blue,
+            directory: Color::Cyan,
+            selected: Color::Green,
+            status: Color::DarkGray,
+            border: Color::DarkGray,
+        }
+    }
+}
+
+impl Config {
+    /// Read and validate a config file
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut config: Config =
+            toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        config
+            .validate()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        config.keymap =
+            Keymap::from_config(&config.keys).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    /// Load from `path` if given, otherwise from the default location if a
+    /// file exists there, otherwise use the built-in defaults
+    pub fn load_or_default(path: Option<&Path>) -> Result<Config, String> {
+        match path {
+            Some(path) => Config::load(path),
+            None => match default_path() {
+                Some(path) if path.is_file() => Config::load(&path),
+                _ => Ok(Config::default()),
+            },
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(10..=90).contains(&self.layout.split) {
+            return Err(format!(
+                "layout.split must be between 10 and 90, got {}",
+                self.layout.split
+            ));
+        }
+        if self.preview.max_bytes == 0 {
+            return Err("preview.max_bytes must be greater than 0".to_string());
+        }
+        if self.preview.scroll_lines == 0 || self.preview.mouse_scroll_lines == 0 {
+            return Err("preview scroll amounts must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn tree_options(&self) -> TreeOptions {
+        TreeOptions {
+            show_hidden: self.general.show_hidden,
+            respect_ignore: self.general.respect_ignore,
+            sort: self.general.sort,
+            revealed: None,
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/browse`, falling back to `~/.config/browse`
+pub fn config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| dirs::home_dir().map(|h| h.join(".config")))?;
+    Some(base.join("browse"))
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// Detects changes to the config file by polling its modification time
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: PathBuf) -> Self {
+        let modified = mtime(&path);
+        Self { path, modified }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns true once each time the file's mtime changes (including
+    /// the file being created or deleted)
+    pub fn changed(&mut self) -> bool {
+        let modified = mtime(&self.path);
+        if modified != self.modified {
+            self.modified = modified;
+            return true;
+        }
+        false
+    }
+}
+
+/// Condense a (possibly multi-line, with source snippet) error into a
+/// single line for the status bar
+pub fn summarize_error(error: &str) -> String {
+    let mut lines = error.lines().map(str::trim).filter(|l| !l.is_empty());
+    let first = lines.next().unwrap_or_default();
+    match lines.next_back() {
+        Some(last) if !last.starts_with('|') => format!("{} {}", first, last),
+        _ => first.to_string(),
+    }
+}
+
+fn mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The built-in mappings, with the configured ones added or overriding them
+fn with_default_extensions<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, String>, D::Error> {
+    let mut map = mapping(DEFAULT_EXTENSIONS);
+    map.extend(BTreeMap::deserialize(deserializer)?);
+    Ok(map)
+}
+
+fn with_default_filenames<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, String>, D::Error> {
+    let mut map = mapping(DEFAULT_FILENAMES);
+    map.extend(BTreeMap::deserialize(deserializer)?);
+    Ok(map)
+}
+
+/// Accepts color names ("cyan", "darkgray"), 256-color indices ("208")
+/// and hex ("#ff8800")
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    Color::from_str(&s).map_err(|_| {
+        serde::de::Error::custom(format!(
+            "invalid color '{}' (expected a name like \"cyan\", an index 0-255 or \"#rrggbb\")",
+            s
+        ))
+    })
+}
@@ -0,0 +1,192 @@
+use goblin::elf::dynamic::DF_1_PIE;
+use goblin::elf::header::{self, ET_CORE, ET_DYN, ET_EXEC, ET_REL};
+use goblin::elf::note::NT_GNU_BUILD_ID;
+use goblin::elf::section_header;
+use goblin::elf::Elf;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+
+use crate::preview::format_size;
+
+const BOLD: Style = Style::new().add_modifier(Modifier::BOLD);
+const DIM: Style = Style::new().add_modifier(Modifier::DIM);
+
+/// Width of the field names in the summary
+const LABEL_WIDTH: usize = 13;
+
+/// What an ELF file's headers say about it
+pub struct ElfInfo {
+    pub bits: u8,
+    pub little_endian: bool,
+    pub kind: &'static str,
+    pub machine: String,
+    pub entry: u64,
+    pub interpreter: Option<String>,
+    pub soname: Option<String>,
+    /// Libraries from `DT_NEEDED` entries, in load order
+    pub needed: Vec<String>,
+    pub sections: Vec<Section>,
+    /// No `.symtab` section
+    pub stripped: bool,
+    /// The GNU build ID note, in hex
+    pub build_id: Option<String>,
+    pub size: u64,
+    /// The summary as shown in the preview
+    pub lines: Vec<Line<'static>>,
+}
+
+pub struct Section {
+    pub name: String,
+    pub kind: String,
+    pub size: u64,
+    pub address: u64,
+}
+
+/// Read the headers of the ELF file in `bytes`
+pub fn parse(bytes: &[u8]) -> Result<ElfInfo, String> {
+    let elf = Elf::parse(bytes).map_err(|e| e.to_string())?;
+
+    let pie = elf
+        .dynamic
+        .as_ref()
+        .is_some_and(|dynamic| dynamic.info.flags_1 & DF_1_PIE != 0);
+    let kind = match elf.header.e_type {
+        ET_EXEC => "executable",
+        // Position-independent executables are shared objects too. Some
+        // libraries (libc) have an interpreter, but they also have a SONAME.
+        ET_DYN if pie || (elf.interpreter.is_some() && elf.soname.is_none()) => {
+            "position-independent executable"
+        }
+        ET_DYN => "shared library",
+        ET_REL => "relocatable object",
+        ET_CORE => "core dump",
+        _ => "unknown type",
+    };
+
+    let sections: Vec<Section> = elf
+        .section_headers
+        .iter()
+        .filter(|sh| sh.sh_type != section_header::SHT_NULL)
+        .map(|sh| Section {
+            name: elf.shdr_strtab.get_at(sh.sh_name).unwrap_or("?").to_string(),
+            kind: section_header::sht_to_str(sh.sh_type).trim_start_matches("SHT_").to_string(),
+            size: sh.sh_size,
+            address: sh.sh_addr,
+        })
+        .collect();
+
+    let build_id = elf
+        .iter_note_sections(bytes, None)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .find(|note| note.n_type == NT_GNU_BUILD_ID && note.name == "GNU")
+        .map(|note| note.desc.iter().map(|b| format!("{:02x}", b)).collect());
+
+    let mut info = ElfInfo {
+        bits: if elf.is_64 { 64 } else { 32 },
+        little_endian: elf.little_endian,
+        kind,
+        machine: machine_name(elf.header.e_machine),
+        entry: elf.entry,
+        interpreter: elf.interpreter.map(str::to_string),
+        soname: elf.soname.map(str::to_string),
+        needed: elf.libraries.iter().map(|l| l.to_string()).collect(),
+        stripped: !sections.iter().any(|s| s.name == ".symtab"),
+        sections,
+        build_id,
+        size: bytes.len() as u64,
+        lines: Vec::new(),
+    };
+    info.lines = info.render();
+    Ok(info)
+}
+
+/// Common architectures by their usual names, others as goblin names them
+fn machine_name(machine: u16) -> String {
+    match machine {
+        header::EM_X86_64 => "x86-64".to_string(),
+        header::EM_386 => "x86".to_string(),
+        header::EM_AARCH64 => "AArch64".to_string(),
+        header::EM_ARM => "ARM".to_string(),
+        header::EM_RISCV => "RISC-V".to_string(),
+        header::EM_PPC64 => "PowerPC64".to_string(),
+        header::EM_S390 => "IBM S/390".to_string(),
+        other => header::machine_to_str(other).to_string(),
+    }
+}
+
+impl ElfInfo {
+    /// The summary, then the dependencies and the section table
+    fn render(&self) -> Vec<Line<'static>> {
+        let endian = if self.little_endian { "LSB" } else { "MSB" };
+        let title = format!(
+            "ELF {}-bit {} {}, {}, {}",
+            self.bits,
+            endian,
+            self.kind,
+            self.machine,
+            format_size(self.size)
+        );
+        let mut lines = vec![Line::from(Span::styled(title, BOLD)), Line::default()];
+
+        let mut field = |label: &str, value: String| {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{:<width$}", label, width = LABEL_WIDTH), DIM),
+                Span::raw(value),
+            ]));
+        };
+        field("Type", self.kind.to_string());
+        field("Machine", self.machine.clone());
+        field("Entry point", format!("{:#x}", self.entry));
+        if let Some(interpreter) = &self.interpreter {
+            field("Interpreter", interpreter.clone());
+        }
+        if let Some(soname) = &self.soname {
+            field("SONAME", soname.clone());
+        }
+        field("Build ID", self.build_id.clone().unwrap_or_else(|| "none".to_string()));
+        field(
+            "Stripped",
+            if self.stripped { "yes" } else { "no (has a symbol table)" }.to_string(),
+        );
+
+        if !self.needed.is_empty() {
+            lines.push(Line::default());
+            lines.push(Line::from(Span::styled(format!("Dependencies ({})", self.needed.len()), BOLD)));
+            lines.extend(self.needed.iter().map(|lib| Line::from(format!("  {}", lib))));
+        }
+
+        lines.push(Line::default());
+        if self.sections.is_empty() {
+            lines.push(Line::from(Span::styled("No section headers", BOLD)));
+            return lines;
+        }
+        lines.push(Line::from(Span::styled(format!("Sections ({})", self.sections.len()), BOLD)));
+        let name_width = self.sections.iter().map(|s| s.name.len()).max().unwrap_or(0).max(4);
+        let kind_width = self.sections.iter().map(|s| s.kind.len()).max().unwrap_or(0).max(4);
+        lines.push(Line::from(Span::styled(
+            format!(
+                "  {:<nw$}  {:<kw$}  {:>10}  Address",
+                "Name",
+                "Type",
+                "Size",
+                nw = name_width,
+                kw = kind_width
+            ),
+            DIM,
+        )));
+        for section in &self.sections {
+            lines.push(Line::from(format!(
+                "  {:<nw$}  {:<kw$}  {:>10}  {:#x}",
+                section.name,
+                section.kind,
+                format_size(section.size),
+                section.address,
+                nw = name_width,
+                kw = kind_width
+            )));
+        }
+        lines
+    }
+}
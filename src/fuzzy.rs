@@ -0,0 +1,54 @@
+/// Match `query` as a case-insensitive subsequence of `candidate`.
+///
+/// Returns a score (higher is better) and the char indices of `candidate`
+/// that matched, or `None` if not every query character was found.
+/// Consecutive matches and matches at the start of words score higher.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let chars: Vec<char> = candidate.chars().collect();
+
+    let mut positions = Vec::with_capacity(query.len());
+    let mut score: i64 = 0;
+    let mut qi = 0;
+    let mut prev_match: Option<usize> = None;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if qi == query.len() {
+            break;
+        }
+        if !c.to_lowercase().eq(std::iter::once(query[qi])) {
+            continue;
+        }
+
+        score += 1;
+        if prev_match == Some(i.wrapping_sub(1)) {
+            score += 5;
+        }
+        let word_start = i == 0 || !chars[i - 1].is_alphanumeric();
+        if word_start {
+            score += 8;
+        }
+        if i == 0 {
+            score += 4;
+        }
+        // Penalize gaps between matches
+        if let Some(prev) = prev_match {
+            score -= (i - prev - 1).min(10) as i64;
+        }
+
+        positions.push(i);
+        prev_match = Some(i);
+        qi += 1;
+    }
+
+    if qi < query.len() {
+        return None;
+    }
+    // Prefer shorter candidates when everything else is equal
+    score -= (chars.len() / 8) as i64;
+    Some((score, positions))
+}
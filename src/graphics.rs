@@ -0,0 +1,325 @@
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Cursor, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use crossterm::cursor::MoveTo;
+use crossterm::queue;
+use image::imageops::{self, FilterType};
+use image::{ImageFormat, ImageReader, RgbaImage};
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use serde::Deserialize;
+
+/// Decoded images are downscaled to fit within this many pixels per side
+const MAX_DIMENSION: u32 = 1600;
+
+/// Cell size assumed when the terminal doesn't report its pixel size
+const DEFAULT_CELL_SIZE: (u32, u32) = (10, 20);
+
+/// Kitty accepts image data in chunks of at most 4096 base64 bytes
+const KITTY_CHUNK: usize = 4096;
+
+/// How images are drawn in the preview pane
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageProtocol {
+    /// Pick one based on the terminal
+    #[default]
+    Auto,
+    Kitty,
+    Iterm2,
+    Sixel,
+    /// Unicode half blocks with truecolor, works in any terminal
+    Halfblocks,
+}
+
+impl ImageProtocol {
+    /// Replace `Auto` with the protocol detected for this terminal
+    pub fn resolve(self) -> ImageProtocol {
+        match self {
+            ImageProtocol::Auto => detect(),
+            protocol => protocol,
+        }
+    }
+}
+
+/// Guess the best supported protocol from the environment. Multiplexers
+/// don't pass graphics through reliably, so they get half blocks.
+fn detect() -> ImageProtocol {
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+    let term = var("TERM");
+    let term_program = var("TERM_PROGRAM");
+
+    if std::env::var_os("TMUX").is_some() || term.starts_with("screen") {
+        return ImageProtocol::Halfblocks;
+    }
+    if std::env::var_os("KITTY_WINDOW_ID").is_some()
+        || term == "xterm-kitty"
+        || term == "xterm-ghostty"
+        || term_program == "ghostty"
+    {
+        return ImageProtocol::Kitty;
+    }
+    if term_program == "iTerm.app"
+        || term_program == "WezTerm"
+        || var("LC_TERMINAL") == "iTerm2"
+    {
+        return ImageProtocol::Iterm2;
+    }
+    if term.starts_with("foot") || term.starts_with("mlterm") || term_program == "contour" {
+        return ImageProtocol::Sixel;
+    }
+    ImageProtocol::Halfblocks
+}
+
+/// Decode an image (the first frame, for animations). Returns the original
+/// dimensions and the pixels, downscaled if the image is very large.
+pub fn decode(bytes: &[u8]) -> Result<((u32, u32), RgbaImage), String> {
+    decode_from(ImageReader::new(Cursor::new(bytes)))
+}
+
+fn decode_from<R: BufRead + Seek>(reader: ImageReader<R>) -> Result<((u32, u32), RgbaImage), String> {
+    let image = reader
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?
+        .decode()
+        .map_err(|e| e.to_string())?;
+    let dimensions = (image.width(), image.height());
+    let image = if image.width() > MAX_DIMENSION || image.height() > MAX_DIMENSION {
+        image.thumbnail(MAX_DIMENSION, MAX_DIMENSION)
+    } else {
+        image
+    };
+    Ok((dimensions, image.to_rgba8()))
+}
+
+/// Largest size within `max_width`×`max_height` with the image's aspect
+/// ratio. Images are never scaled up.
+fn fit(width: u32, height: u32, max_width: u32, max_height: u32) -> (u32, u32) {
+    let scale = (max_width as f64 / width as f64)
+        .min(max_height as f64 / height as f64)
+        .min(1.0);
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+/// Render an image with `▀` characters, each cell showing two pixels: the
+/// top one as the foreground color and the bottom one as the background
+pub fn halfblocks(image: &RgbaImage, cols: u16, rows: u16) -> Vec<Line<'static>> {
+    if cols == 0 || rows == 0 {
+        return Vec::new();
+    }
+    let (width, height) = fit(image.width(), image.height(), cols as u32, rows as u32 * 2);
+    let scaled = imageops::resize(image, width, height, FilterType::Triangle);
+
+    let color = |x: u32, y: u32| -> Option<Color> {
+        if y >= height {
+            return None;
+        }
+        let [r, g, b, a] = scaled.get_pixel(x, y).0;
+        (a >= 128).then_some(Color::Rgb(r, g, b))
+    };
+
+    (0..height.div_ceil(2))
+        .map(|row| {
+            let spans: Vec<Span> = (0..width)
+                .map(|x| match (color(x, row * 2), color(x, row * 2 + 1)) {
+                    (Some(top), Some(bottom)) => Span::styled("▀", Style::default().fg(top).bg(bottom)),
+                    (Some(top), None) => Span::styled("▀", Style::default().fg(top)),
+                    (None, Some(bottom)) => Span::styled("▄", Style::default().fg(bottom)),
+                    (None, None) => Span::raw(" "),
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// An image to draw over the preview pane with a graphics protocol
+pub struct Placement<'a> {
+    pub path: &'a Path,
+    pub image: &'a RgbaImage,
+    pub area: Rect,
+    pub protocol: ImageProtocol,
+}
+
+/// Tracks the image drawn with a graphics protocol. The terminal keeps it
+/// on screen outside of ratatui's buffer, so it's only sent again when the
+/// file or the pane changes, and has to be erased explicitly.
+#[derive(Default)]
+pub struct ImageLayer {
+    shown: Option<(PathBuf, Rect, ImageProtocol)>,
+}
+
+impl ImageLayer {
+    /// Remove the image on screen if it isn't `wanted`. Returns true if the
+    /// screen must be repainted to erase it (Kitty images can be deleted,
+    /// Sixel and iTerm2 images are overwritten only by redrawing the cells).
+    pub fn clear_stale(&mut self, out: &mut impl Write, wanted: Option<&Placement>) -> io::Result<bool> {
+        let Some((path, area, protocol)) = &self.shown else {
+            return Ok(false);
+        };
+        if let Some(wanted) = wanted
+            && wanted.path == path
+            && wanted.area == *area
+            && wanted.protocol == *protocol
+        {
+            return Ok(false);
+        }
+
+        let protocol = *protocol;
+        self.shown = None;
+        if protocol == ImageProtocol::Kitty {
+            self.clear_all(out)?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Draw `wanted` unless it's already on screen
+    pub fn show(&mut self, out: &mut impl Write, wanted: Option<Placement>) -> io::Result<()> {
+        let Some(placement) = wanted else {
+            return Ok(());
+        };
+        if self.shown.is_some() || placement.area.width == 0 || placement.area.height == 0 {
+            return Ok(());
+        }
+
+        let (cell_width, cell_height) = cell_size();
+        let (width, height) = fit(
+            placement.image.width(),
+            placement.image.height(),
+            placement.area.width as u32 * cell_width,
+            placement.area.height as u32 * cell_height,
+        );
+        let scaled = imageops::resize(placement.image, width, height, FilterType::Triangle);
+        let sequence = match placement.protocol {
+            ImageProtocol::Kitty => kitty(&scaled)?,
+            ImageProtocol::Iterm2 => iterm2(&scaled)?,
+            ImageProtocol::Sixel => sixel(&scaled),
+            ImageProtocol::Auto | ImageProtocol::Halfblocks => return Ok(()),
+        };
+
+        queue!(out, MoveTo(placement.area.x, placement.area.y))?;
+        out.write_all(sequence.as_bytes())?;
+        out.flush()?;
+        self.shown = Some((placement.path.to_path_buf(), placement.area, placement.protocol));
+        Ok(())
+    }
+
+    /// Delete all Kitty images, e.g. before exiting
+    pub fn clear_all(&mut self, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "\x1b_Ga=d,d=A,q=2\x1b\\")?;
+        out.flush()
+    }
+}
+
+/// Pixel size of a terminal cell, if the terminal reports it
+fn cell_size() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns) as u32,
+            (size.height / size.rows) as u32,
+        ),
+        _ => DEFAULT_CELL_SIZE,
+    }
+}
+
+fn png_base64(image: &RgbaImage) -> io::Result<String> {
+    let mut png = Cursor::new(Vec::new());
+    image
+        .write_to(&mut png, ImageFormat::Png)
+        .map_err(io::Error::other)?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(png.into_inner()))
+}
+
+/// Kitty graphics protocol: transmit and display a PNG, without moving the
+/// cursor and without a reply from the terminal
+fn kitty(image: &RgbaImage) -> io::Result<String> {
+    let data = png_base64(image)?;
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if i == 0 {
+            let _ = write!(out, "\x1b_Ga=T,f=100,q=2,C=1,m={};{}\x1b\\", more, chunk);
+        } else {
+            let _ = write!(out, "\x1b_Gm={};{}\x1b\\", more, chunk);
+        }
+    }
+    Ok(out)
+}
+
+/// iTerm2 inline image protocol (also understood by WezTerm)
+fn iterm2(image: &RgbaImage) -> io::Result<String> {
+    let data = png_base64(image)?;
+    Ok(format!(
+        "\x1b]1337;File=inline=1;width={}px;height={}px;preserveAspectRatio=1;doNotMoveCursor=1:{}\x07",
+        image.width(),
+        image.height(),
+        data
+    ))
+}
+
+/// Sixel graphics with a fixed 6×6×6 color cube. Transparent pixels are
+/// left unpainted.
+fn sixel(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let level = |v: u8| (v as usize * 5 + 127) / 255;
+
+    // P2=1: pixels that aren't set keep the background
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    for i in 0..216 {
+        let (r, g, b) = (i / 36, i / 6 % 6, i % 6);
+        let _ = write!(out, "#{};2;{};{};{}", i, r * 20, g * 20, b * 20);
+    }
+
+    for top in (0..height).step_by(6) {
+        // Sixel bit patterns of this band, per palette color
+        let mut bands: Vec<Option<Vec<u8>>> = vec![None; 216];
+        for dy in 0..(height - top).min(6) {
+            for x in 0..width {
+                let [r, g, b, a] = image.get_pixel(x, top + dy).0;
+                if a < 128 {
+                    continue;
+                }
+                let color = level(r) * 36 + level(g) * 6 + level(b);
+                bands[color].get_or_insert_with(|| vec![0; width as usize])[x as usize] |= 1 << dy;
+            }
+        }
+
+        for (color, bits) in bands.iter().enumerate() {
+            if let Some(bits) = bits {
+                let _ = write!(out, "#{}", color);
+                push_sixels(&mut out, bits);
+                out.push('$');
+            }
+        }
+        if top + 6 < height {
+            out.push('-');
+        }
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+/// Append sixel characters for one color of a band, run-length encoded
+fn push_sixels(out: &mut String, bits: &[u8]) {
+    let len = bits.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    let mut i = 0;
+    while i < len {
+        let run = bits[i..len].iter().take_while(|&&b| b == bits[i]).count();
+        let c = (63 + bits[i]) as char;
+        if run > 3 {
+            let _ = write!(out, "!{}{}", run, c);
+        } else {
+            (0..run).for_each(|_| out.push(c));
+        }
+        i += run;
+    }
+}
@@ -0,0 +1,165 @@
+use std::sync::{Arc, Mutex};
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use syntect::highlighting::{self, FontStyle, HighlightIterator, HighlightState, Highlighter, Theme};
+use syntect::parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
+
+/// Parser state is saved at the start of every this-many-th line
+const CHECKPOINT_LINES: usize = 128;
+
+/// Lines highlighted beyond the visible window, so scrolling a little
+/// further doesn't need another pass
+const MARGIN_LINES: usize = 64;
+
+/// Source code highlighted on demand. Only the lines that are displayed
+/// (plus a margin) get highlighted, and the parser state is checkpointed as
+/// it goes, so jumping deep into the file resumes from the nearest
+/// checkpoint instead of parsing from the top again.
+pub struct Highlighted {
+    text: String,
+    /// Byte offset where each line starts
+    line_starts: Vec<usize>,
+    syntax_set: Arc<SyntaxSet>,
+    theme: Theme,
+    state: Mutex<State>,
+}
+
+struct State {
+    /// Parser state at the start of line `i * CHECKPOINT_LINES`
+    checkpoints: Vec<(ParseState, HighlightState)>,
+    /// Lines highlighted so far
+    lines: Vec<Option<Line<'static>>>,
+}
+
+impl Highlighted {
+    pub fn new(text: String, syntax: &SyntaxReference, syntax_set: Arc<SyntaxSet>, theme: &Theme) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(
+            text.match_indices('\n')
+                .map(|(i, _)| i + 1)
+                .filter(|&start| start < text.len()),
+        );
+        let highlighter = Highlighter::new(theme);
+        let start = (
+            ParseState::new(syntax),
+            HighlightState::new(&highlighter, ScopeStack::new()),
+        );
+        let line_count = line_starts.len();
+        Self {
+            text,
+            line_starts,
+            syntax_set,
+            theme: theme.clone(),
+            state: Mutex::new(State {
+                checkpoints: vec![start],
+                lines: vec![None; line_count],
+            }),
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Rough memory use, for the preview cache. It grows as more of the
+    /// file is highlighted.
+    pub fn weight(&self) -> usize {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let lines: usize = state
+            .lines
+            .iter()
+            .flatten()
+            .flat_map(|line| &line.spans)
+            .map(|span| span.content.len() + std::mem::size_of::<Span>())
+            .sum();
+        let checkpoints = state.checkpoints.len() * std::mem::size_of::<(ParseState, HighlightState)>();
+        self.text.len() + self.line_starts.len() * std::mem::size_of::<usize>() + lines + checkpoints
+    }
+
+    /// Line `i`, including its newline (the syntaxes expect one)
+    fn line(&self, i: usize) -> &str {
+        let end = self.line_starts.get(i + 1).copied().unwrap_or(self.text.len());
+        &self.text[self.line_starts[i]..end]
+    }
+
+    /// Highlighted lines `first..first + count`
+    pub fn lines(&self, first: usize, count: usize) -> Vec<Line<'static>> {
+        let end = first.saturating_add(count).min(self.line_count());
+        let first = first.min(end);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.lines[first..end].iter().any(Option::is_none) {
+            self.highlight(
+                &mut state,
+                first.saturating_sub(MARGIN_LINES),
+                (end + MARGIN_LINES).min(self.line_count()),
+            );
+        }
+        state.lines[first..end]
+            .iter()
+            .map(|line| line.clone().unwrap_or_default())
+            .collect()
+    }
+
+    /// Highlight lines `start..stop`, parsing from the last checkpoint
+    /// before `start` and saving new checkpoints on the way
+    fn highlight(&self, state: &mut State, start: usize, stop: usize) {
+        let highlighter = Highlighter::new(&self.theme);
+        let checkpoint = (start / CHECKPOINT_LINES).min(state.checkpoints.len() - 1);
+        let (mut parse_state, mut highlight_state) = state.checkpoints[checkpoint].clone();
+
+        for i in checkpoint * CHECKPOINT_LINES..stop {
+            if i.is_multiple_of(CHECKPOINT_LINES) && i / CHECKPOINT_LINES == state.checkpoints.len() {
+                state
+                    .checkpoints
+                    .push((parse_state.clone(), highlight_state.clone()));
+            }
+
+            let line = self.line(i);
+            // A line the syntax can't parse is left unstyled
+            let ops = parse_state
+                .parse_line(line, &self.syntax_set)
+                .unwrap_or_default();
+            let ranges = HighlightIterator::new(&mut highlight_state, &ops, line, &highlighter);
+            if i >= start && state.lines[i].is_none() {
+                let ranges: Vec<_> = ranges.collect();
+                state.lines[i] = Some(styled_line(&ranges));
+            } else {
+                // Still needed to keep the highlight state in step
+                ranges.for_each(drop);
+            }
+        }
+    }
+}
+
+/// Convert a highlighted line from syntect, dropping its line ending. The
+/// theme's background is left to the terminal.
+pub fn styled_line(ranges: &[(highlighting::Style, &str)]) -> Line<'static> {
+    let spans: Vec<Span<'static>> = ranges
+        .iter()
+        .map(|(style, text)| {
+            let text = text.trim_end_matches(['\n', '\r']);
+            Span::styled(text.to_string(), span_style(*style))
+        })
+        .filter(|span| !span.content.is_empty())
+        .collect();
+    Line::from(spans)
+}
+
+fn span_style(style: highlighting::Style) -> Style {
+    let mut result = Style::new().fg(Color::Rgb(
+        style.foreground.r,
+        style.foreground.g,
+        style.foreground.b,
+    ));
+    if style.font_style.contains(FontStyle::BOLD) {
+        result = result.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        result = result.add_modifier(Modifier::ITALIC);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        result = result.add_modifier(Modifier::UNDERLINED);
+    }
+    result
+}
@@ -0,0 +1,669 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+/// Everything a key can be bound to
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Quit,
+    MoveDown,
+    MoveUp,
+    JumpTop,
+    JumpBottom,
+    ToggleExpand,
+    CollapseOrParent,
+    ToggleHidden,
+    ToggleIgnore,
+    CycleSort,
+    Refresh,
+    YankPath,
+    ScrollPreviewDown,
+    ScrollPreviewUp,
+    ScrollPreviewHalfDown,
+    ScrollPreviewHalfUp,
+    ScrollPreviewTop,
+    ScrollPreviewBottom,
+    ScrollPreviewLeft,
+    ScrollPreviewRight,
+    ToggleFollow,
+    PickTheme,
+    NextLink,
+    PrevLink,
+    FollowLink,
+    Outline,
+    ToggleNode,
+    CollapseNodes,
+    ExpandNodes,
+    TogglePreview,
+    GrowTree,
+    ShrinkTree,
+    ShowHelp,
+    CommandPalette,
+    ToggleDebug,
+}
+
+impl Action {
+    /// Every action, grouped by category in display order
+    pub const ALL: &[Action] = &[
+        Action::MoveDown,
+        Action::MoveUp,
+        Action::JumpTop,
+        Action::JumpBottom,
+        Action::ToggleExpand,
+        Action::CollapseOrParent,
+        Action::ToggleHidden,
+        Action::ToggleIgnore,
+        Action::CycleSort,
+        Action::Refresh,
+        Action::YankPath,
+        Action::ScrollPreviewDown,
+        Action::ScrollPreviewUp,
+        Action::ScrollPreviewHalfDown,
+        Action::ScrollPreviewHalfUp,
+        Action::ScrollPreviewTop,
+        Action::ScrollPreviewBottom,
+        Action::ScrollPreviewLeft,
+        Action::ScrollPreviewRight,
+        Action::ToggleFollow,
+        Action::PickTheme,
+        Action::NextLink,
+        Action::PrevLink,
+        Action::FollowLink,
+        Action::Outline,
+        Action::ToggleNode,
+        Action::CollapseNodes,
+        Action::ExpandNodes,
+        Action::TogglePreview,
+        Action::GrowTree,
+        Action::ShrinkTree,
+        Action::ShowHelp,
+        Action::CommandPalette,
+        Action::ToggleDebug,
+        Action::Quit,
+    ];
+
+    /// Name used in the config file's `[keys]` table
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::MoveDown => "move_down",
+            Action::MoveUp => "move_up",
+            Action::JumpTop => "jump_top",
+            Action::JumpBottom => "jump_bottom",
+            Action::ToggleExpand => "toggle_expand",
+            Action::CollapseOrParent => "collapse_or_parent",
+            Action::ToggleHidden => "toggle_hidden",
+            Action::ToggleIgnore => "toggle_ignore",
+            Action::CycleSort => "cycle_sort",
+            Action::Refresh => "refresh",
+            Action::YankPath => "yank_path",
+            Action::ScrollPreviewDown => "scroll_preview_down",
+            Action::ScrollPreviewUp => "scroll_preview_up",
+            Action::ScrollPreviewHalfDown => "scroll_preview_half_down",
+            Action::ScrollPreviewHalfUp => "scroll_preview_half_up",
+            Action::ScrollPreviewTop => "scroll_preview_top",
+            Action::ScrollPreviewBottom => "scroll_preview_bottom",
+            Action::ScrollPreviewLeft => "scroll_preview_left",
+            Action::ScrollPreviewRight => "scroll_preview_right",
+            Action::ToggleFollow => "toggle_follow",
+            Action::PickTheme => "pick_theme",
+            Action::NextLink => "next_link",
+            Action::PrevLink => "prev_link",
+            Action::FollowLink => "follow_link",
+            Action::Outline => "outline",
+            Action::ToggleNode => "toggle_node",
+            Action::CollapseNodes => "collapse_nodes",
+            Action::ExpandNodes => "expand_nodes",
+            Action::TogglePreview => "toggle_preview",
+            Action::GrowTree => "grow_tree",
+            Action::ShrinkTree => "shrink_tree",
+            Action::ShowHelp => "show_help",
+            Action::CommandPalette => "command_palette",
+            Action::ToggleDebug => "toggle_debug",
+        }
+    }
+
+    /// Heading the action is listed under in the help overlay
+    pub fn category(self) -> &'static str {
+        match self {
+            Action::MoveDown | Action::MoveUp | Action::JumpTop | Action::JumpBottom => {
+                "Navigation"
+            }
+            Action::ToggleExpand
+            | Action::CollapseOrParent
+            | Action::ToggleHidden
+            | Action::ToggleIgnore
+            | Action::CycleSort
+            | Action::Refresh
+            | Action::YankPath => "Tree",
+            Action::ScrollPreviewDown
+            | Action::ScrollPreviewUp
+            | Action::ScrollPreviewHalfDown
+            | Action::ScrollPreviewHalfUp
+            | Action::ScrollPreviewTop
+            | Action::ScrollPreviewBottom
+            | Action::ScrollPreviewLeft
+            | Action::ScrollPreviewRight
+            | Action::ToggleFollow
+            | Action::PickTheme
+            | Action::NextLink
+            | Action::PrevLink
+            | Action::FollowLink
+            | Action::Outline
+            | Action::ToggleNode
+            | Action::CollapseNodes
+            | Action::ExpandNodes => "Preview",
+            Action::TogglePreview | Action::GrowTree | Action::ShrinkTree => "Layout",
+            Action::ShowHelp | Action::CommandPalette | Action::ToggleDebug | Action::Quit => "General",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::MoveDown => "Move selection down",
+            Action::MoveUp => "Move selection up",
+            Action::JumpTop => "Jump to the first entry",
+            Action::JumpBottom => "Jump to the last entry",
+            Action::ToggleExpand => "Expand or collapse directory",
+            Action::CollapseOrParent => "Collapse directory or go to parent",
+            Action::ToggleHidden => "Show or hide hidden files",
+            Action::ToggleIgnore => "Show or hide files matched by .gitignore",
+            Action::CycleSort => "Cycle sort order",
+            Action::Refresh => "Reload the tree from disk",
+            Action::YankPath => "Copy path to clipboard",
+            Action::ScrollPreviewDown => "Scroll preview down",
+            Action::ScrollPreviewUp => "Scroll preview up",
+            Action::ScrollPreviewHalfDown => "Scroll preview down half a page",
+            Action::ScrollPreviewHalfUp => "Scroll preview up half a page",
+            Action::ScrollPreviewTop => "Scroll preview to the start",
+            Action::ScrollPreviewBottom => "Scroll preview to the end",
+            Action::ScrollPreviewLeft => "Scroll a table preview one column left",
+            Action::ScrollPreviewRight => "Scroll a table preview one column right",
+            Action::ToggleFollow => "Follow the previewed file as it grows",
+            Action::PickTheme => "Pick a syntax theme with live preview",
+            Action::NextLink => "Select the next link in a markdown preview",
+            Action::PrevLink => "Select the previous link in a markdown preview",
+            Action::FollowLink => "Follow the selected link (also Enter on a file)",
+            Action::Outline => "Jump to a heading of a markdown preview",
+            Action::ToggleNode => "Fold or unfold the selected JSON/YAML/TOML node (also Enter on a file)",
+            Action::CollapseNodes => "Fold every JSON/YAML/TOML node",
+            Action::ExpandNodes => "Unfold every JSON/YAML/TOML node",
+            Action::TogglePreview => "Show or hide the preview pane",
+            Action::GrowTree => "Widen the tree pane",
+            Action::ShrinkTree => "Narrow the tree pane",
+            Action::ShowHelp => "Show key bindings",
+            Action::CommandPalette => "Open the command palette",
+            Action::ToggleDebug => "Show preview cache statistics in the status bar",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().copied().find(|a| a.name() == name)
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q", "<C-c>"],
+            Action::MoveDown => &["j", "<Down>"],
+            Action::MoveUp => &["k", "<Up>"],
+            Action::JumpTop => &["gg", "<Home>"],
+            Action::JumpBottom => &["G", "<End>"],
+            Action::ToggleExpand => &["l", "<Right>", "<Enter>"],
+            Action::CollapseOrParent => &["h", "<Left>"],
+            Action::ToggleHidden => &["."],
+            Action::ToggleIgnore => &["i"],
+            Action::CycleSort => &["s"],
+            Action::Refresh => &["r"],
+            Action::YankPath => &["y"],
+            Action::ScrollPreviewDown => &["J"],
+            Action::ScrollPreviewUp => &["K"],
+            Action::ScrollPreviewHalfDown => &["d"],
+            Action::ScrollPreviewHalfUp => &["u"],
+            Action::ScrollPreviewTop => &["["],
+            Action::ScrollPreviewBottom => &["]"],
+            Action::ScrollPreviewLeft => &["H"],
+            Action::ScrollPreviewRight => &["L"],
+            Action::ToggleFollow => &["F"],
+            Action::PickTheme => &["T"],
+            Action::NextLink => &["<Tab>"],
+            Action::PrevLink => &["<S-Tab>"],
+            Action::FollowLink => &["o"],
+            Action::Outline => &["O"],
+            Action::ToggleNode => &["za"],
+            Action::CollapseNodes => &["zM"],
+            Action::ExpandNodes => &["zR"],
+            Action::TogglePreview => &["p"],
+            Action::GrowTree => &[">"],
+            Action::ShrinkTree => &["<lt>"],
+            Action::ShowHelp => &["?"],
+            Action::CommandPalette => &["<C-p>"],
+            Action::ToggleDebug => &["<F12>"],
+        }
+    }
+}
+
+/// A single key press with modifiers
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // Shift is already reflected in the character (and in BackTab), so
+        // drop it to make `J` and `<S-j>` the same chord
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Self { code, modifiers }
+    }
+}
+
+impl From<KeyEvent> for KeyChord {
+    fn from(key: KeyEvent) -> Self {
+        KeyChord::new(key.code, key.modifiers)
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let named = match self.code {
+            KeyCode::Char('<') => Some("lt".to_string()),
+            KeyCode::Char(' ') => Some("Space".to_string()),
+            KeyCode::Char(_) => None,
+            KeyCode::Enter => Some("Enter".to_string()),
+            KeyCode::Esc => Some("Esc".to_string()),
+            KeyCode::Tab => Some("Tab".to_string()),
+            KeyCode::BackTab => Some("S-Tab".to_string()),
+            KeyCode::Backspace => Some("BS".to_string()),
+            KeyCode::Delete => Some("Del".to_string()),
+            KeyCode::Insert => Some("Insert".to_string()),
+            KeyCode::Up => Some("Up".to_string()),
+            KeyCode::Down => Some("Down".to_string()),
+            KeyCode::Left => Some("Left".to_string()),
+            KeyCode::Right => Some("Right".to_string()),
+            KeyCode::Home => Some("Home".to_string()),
+            KeyCode::End => Some("End".to_string()),
+            KeyCode::PageUp => Some("PageUp".to_string()),
+            KeyCode::PageDown => Some("PageDown".to_string()),
+            KeyCode::F(n) => Some(format!("F{}", n)),
+            _ => Some("?".to_string()),
+        };
+
+        let mut prefix = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            prefix.push_str("C-");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            prefix.push_str("A-");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            prefix.push_str("S-");
+        }
+
+        match (named, self.code) {
+            (None, KeyCode::Char(c)) if prefix.is_empty() => write!(f, "{}", c),
+            (None, KeyCode::Char(c)) => write!(f, "<{}{}>", prefix, c),
+            (Some(name), _) => write!(f, "<{}{}>", prefix, name),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+/// Parse a key sequence in vim notation: plain characters stand for
+/// themselves (`gg`, `zc`), special keys and modifiers go in angle
+/// brackets (`<Enter>`, `<C-p>`, `<A-Down>`, `<lt>` for a literal `<`)
+pub fn parse_sequence(s: &str) -> Result<Vec<KeyChord>, String> {
+    let mut chords = Vec::new();
+    let mut rest = s;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '<'
+            && let Some(end) = rest.find('>')
+            && end > 1
+        {
+            chords.push(parse_special(&rest[1..end]).ok_or_else(|| format!("invalid key '{}'", s))?);
+            rest = &rest[end + 1..];
+            continue;
+        }
+        chords.push(KeyChord::new(KeyCode::Char(c), KeyModifiers::NONE));
+        rest = &rest[c.len_utf8()..];
+    }
+
+    if chords.is_empty() {
+        return Err("empty key sequence".to_string());
+    }
+    Ok(chords)
+}
+
+/// Parse the inside of `<...>`, e.g. `C-S-Tab` or `PageDown`
+fn parse_special(inner: &str) -> Option<KeyChord> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut name = inner;
+    // A trailing `-` is the key itself, as in `<C-->`
+    while let Some((prefix, rest)) = name.split_once('-')
+        && !rest.is_empty()
+    {
+        modifiers |= match prefix.to_ascii_lowercase().as_str() {
+            "c" | "ctrl" => KeyModifiers::CONTROL,
+            "a" | "m" | "alt" | "meta" => KeyModifiers::ALT,
+            "s" | "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+        name = rest;
+    }
+
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        // Ctrl combinations arrive as lowercase letters, shifted ones as
+        // the uppercase character
+        let c = if modifiers.contains(KeyModifiers::CONTROL) {
+            c.to_ascii_lowercase()
+        } else if modifiers.contains(KeyModifiers::SHIFT) {
+            let mut upper = c.to_uppercase();
+            match (upper.next(), upper.next()) {
+                (Some(u), None) => u,
+                _ => c,
+            }
+        } else {
+            c
+        };
+        return Some(KeyChord::new(KeyCode::Char(c), modifiers));
+    }
+
+    let code = match name.to_ascii_lowercase().as_str() {
+        "lt" => KeyCode::Char('<'),
+        "gt" => KeyCode::Char('>'),
+        "space" => KeyCode::Char(' '),
+        "enter" | "cr" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+        "tab" => KeyCode::Tab,
+        "bs" | "backspace" => KeyCode::Backspace,
+        "del" | "delete" => KeyCode::Delete,
+        "insert" | "ins" => KeyCode::Insert,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" | "pgup" => KeyCode::PageUp,
+        "pagedown" | "pgdn" => KeyCode::PageDown,
+        other => {
+            let n = other.strip_prefix('f')?.parse().ok()?;
+            if !(1..=24).contains(&n) {
+                return None;
+            }
+            KeyCode::F(n)
+        }
+    };
+    Some(KeyChord::new(code, modifiers))
+}
+
+pub fn format_sequence(chords: &[KeyChord]) -> String {
+    chords.iter().map(KeyChord::to_string).collect()
+}
+
+/// Result of looking up the keys typed so far
+pub enum Lookup {
+    Action(Action),
+    /// The keys start a longer binding; wait for more
+    Prefix,
+    None,
+}
+
+/// Maps key sequences to actions
+#[derive(Clone)]
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyChord>, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::from_config(&BTreeMap::new()).expect("default key bindings are valid")
+    }
+}
+
+impl Keymap {
+    /// Build the keymap from the `[keys]` config table. Each entry replaces
+    /// all default bindings of that action; an empty list unbinds it.
+    pub fn from_config(keys: &BTreeMap<String, KeyList>) -> Result<Keymap, String> {
+        for name in keys.keys() {
+            if Action::from_name(name).is_none() {
+                return Err(format!(
+                    "keys.{}: unknown action (expected one of: {})",
+                    name,
+                    Action::ALL.iter().map(|a| a.name()).collect::<Vec<_>>().join(", ")
+                ));
+            }
+        }
+
+        let mut bindings = Vec::new();
+        for &action in Action::ALL {
+            match keys.get(action.name()) {
+                Some(KeyList(list)) => {
+                    for s in list {
+                        let seq = parse_sequence(s)
+                            .map_err(|e| format!("keys.{}: {}", action.name(), e))?;
+                        bindings.push((seq, action));
+                    }
+                }
+                None => {
+                    for s in action.default_keys() {
+                        let seq = parse_sequence(s).expect("default key bindings are valid");
+                        bindings.push((seq, action));
+                    }
+                }
+            }
+        }
+
+        let keymap = Keymap { bindings };
+        keymap.check_conflicts()?;
+        Ok(keymap)
+    }
+
+    /// A sequence can't be bound twice, and can't be a prefix of another
+    /// sequence since the longer one could then never be typed
+    fn check_conflicts(&self) -> Result<(), String> {
+        for (i, (a, action_a)) in self.bindings.iter().enumerate() {
+            for (b, action_b) in &self.bindings[i + 1..] {
+                if a == b {
+                    if action_a == action_b {
+                        continue;
+                    }
+                    return Err(format!(
+                        "keys: '{}' is bound to both {} and {}",
+                        format_sequence(a),
+                        action_a.name(),
+                        action_b.name()
+                    ));
+                }
+                let (short, short_action, long, long_action) = if a.len() < b.len() {
+                    (a, action_a, b, action_b)
+                } else {
+                    (b, action_b, a, action_a)
+                };
+                if long.starts_with(short) {
+                    return Err(format!(
+                        "keys: '{}' ({}) conflicts with '{}' ({}), which starts with it",
+                        format_sequence(short),
+                        short_action.name(),
+                        format_sequence(long),
+                        long_action.name()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn lookup(&self, keys: &[KeyChord]) -> Lookup {
+        let mut prefix = false;
+        for (seq, action) in &self.bindings {
+            if seq.as_slice() == keys {
+                return Lookup::Action(*action);
+            }
+            if seq.starts_with(keys) {
+                prefix = true;
+            }
+        }
+        if prefix { Lookup::Prefix } else { Lookup::None }
+    }
+
+    /// Key sequences bound to `action`, formatted for display
+    pub fn keys_for(&self, action: Action) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(seq, _)| format_sequence(seq))
+            .collect()
+    }
+
+    /// Table of every action with its effective bindings, for `--list-keys`
+    pub fn listing(&self) -> String {
+        let mut out = String::new();
+        for &action in Action::ALL {
+            let keys = self.keys_for(action);
+            let keys = if keys.is_empty() {
+                "(unbound)".to_string()
+            } else {
+                keys.join(", ")
+            };
+            out.push_str(&format!(
+                "{:<26} {:<20} {}\n",
+                action.name(),
+                keys,
+                action.description()
+            ));
+        }
+        out
+    }
+}
+
+/// One or more key sequences: `"j"` or `["j", "<Down>"]`
+#[derive(Clone, Default)]
+pub struct KeyList(pub Vec<String>);
+
+impl<'de> Deserialize<'de> for KeyList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeyListVisitor;
+
+        impl<'de> Visitor<'de> for KeyListVisitor {
+            type Value = KeyList;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a key sequence or a list of key sequences")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<KeyList, E> {
+                Ok(KeyList(vec![v.to_string()]))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<KeyList, A::Error> {
+                let mut keys = Vec::new();
+                while let Some(key) = seq.next_element::<String>()? {
+                    keys.push(key);
+                }
+                Ok(KeyList(keys))
+            }
+        }
+
+        deserializer.deserialize_any(KeyListVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(code: KeyCode, modifiers: KeyModifiers) -> KeyChord {
+        KeyChord::new(code, modifiers)
+    }
+
+    fn keys(list: &[&str]) -> KeyList {
+        KeyList(list.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn parses_vim_notation() {
+        let g = chord(KeyCode::Char('g'), KeyModifiers::NONE);
+        assert_eq!(parse_sequence("gg"), Ok(vec![g, g]));
+        assert_eq!(
+            parse_sequence("<C-p>"),
+            Ok(vec![chord(KeyCode::Char('p'), KeyModifiers::CONTROL)])
+        );
+        assert_eq!(parse_sequence("<C-P>"), parse_sequence("<C-p>"));
+        assert_eq!(parse_sequence("<lt>"), Ok(vec![chord(KeyCode::Char('<'), KeyModifiers::NONE)]));
+        assert_eq!(
+            parse_sequence("<C-->"),
+            Ok(vec![chord(KeyCode::Char('-'), KeyModifiers::CONTROL)])
+        );
+        assert_eq!(parse_sequence("<S-Tab>"), Ok(vec![chord(KeyCode::BackTab, KeyModifiers::NONE)]));
+        assert_eq!(parse_sequence("<S-j>"), parse_sequence("J"));
+        assert_eq!(
+            parse_sequence("<A-Down>"),
+            Ok(vec![chord(KeyCode::Down, KeyModifiers::ALT)])
+        );
+        // An unclosed bracket is just the character
+        assert_eq!(parse_sequence("<").unwrap().len(), 1);
+
+        for invalid in ["", "<X-p>", "<nope>", "<F25>", "a<Foo>"] {
+            assert!(parse_sequence(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn formats_sequences_back_to_vim_notation() {
+        for s in ["gg", "<C-p>", "<lt>", "<S-Tab>", "J", "<A-Down>", "<F12>"] {
+            assert_eq!(format_sequence(&parse_sequence(s).unwrap()), s);
+        }
+    }
+
+    #[test]
+    fn looks_up_actions_and_prefixes() {
+        let keymap = Keymap::default();
+        let seq = |s| parse_sequence(s).unwrap();
+        assert!(matches!(keymap.lookup(&seq("j")), Lookup::Action(Action::MoveDown)));
+        assert!(matches!(keymap.lookup(&seq("<Down>")), Lookup::Action(Action::MoveDown)));
+        assert!(matches!(keymap.lookup(&seq("g")), Lookup::Prefix));
+        assert!(matches!(keymap.lookup(&seq("gg")), Lookup::Action(Action::JumpTop)));
+        assert!(matches!(keymap.lookup(&seq("gx")), Lookup::None));
+        assert!(matches!(keymap.lookup(&seq("<S-Tab>")), Lookup::Action(Action::PrevLink)));
+    }
+
+    #[test]
+    fn config_replaces_defaults_and_rejects_conflicts() {
+        let mut config = BTreeMap::new();
+        config.insert("move_down".to_string(), keys(&["n"]));
+        config.insert("quit".to_string(), keys(&[]));
+        let keymap = Keymap::from_config(&config).unwrap();
+        assert_eq!(keymap.keys_for(Action::MoveDown), vec!["n"]);
+        assert!(keymap.keys_for(Action::Quit).is_empty());
+        assert!(matches!(keymap.lookup(&[chord(KeyCode::Char('j'), KeyModifiers::NONE)]), Lookup::None));
+
+        let mut config = BTreeMap::new();
+        config.insert("move_down".to_string(), keys(&["k"]));
+        let err = Keymap::from_config(&config).err().unwrap();
+        assert!(err.contains("bound to both"), "{}", err);
+
+        let mut config = BTreeMap::new();
+        config.insert("move_down".to_string(), keys(&["z"]));
+        let err = Keymap::from_config(&config).err().unwrap();
+        assert!(err.contains("starts with it"), "{}", err);
+
+        let mut config = BTreeMap::new();
+        config.insert("fly".to_string(), keys(&["f"]));
+        assert!(Keymap::from_config(&config).err().unwrap().contains("unknown action"));
+
+        let mut config = BTreeMap::new();
+        config.insert("move_down".to_string(), keys(&["<Nope>"]));
+        assert!(Keymap::from_config(&config).err().unwrap().starts_with("keys.move_down:"));
+    }
+}
@@ -1,40 +1,122 @@
 mod ansi;
 mod app;
+mod archive;
+mod binary;
+mod cache;
+mod cli;
+mod config;
+mod elf;
+mod fuzzy;
+mod graphics;
+mod highlight;
+mod keymap;
+mod markdown;
+mod palette;
 mod preview;
+mod sqlite;
+mod stream;
+mod structured;
+mod syntax;
+mod table;
+mod theme;
 mod tree;
 mod ui;
+mod vfs;
+mod worker;
 
-use std::io;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
 
 use crossterm::event::{
-    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton,
-    MouseEventKind,
+    self, DisableMouseCapture, EnableMouseCapture, Event, MouseButton, MouseEventKind,
 };
 use crossterm::execute;
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
 };
-use ratatui::backend::CrosstermBackend;
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::buffer::{Buffer, Cell};
+use ratatui::layout::Rect;
 use ratatui::Terminal;
 
 use app::App;
+use cli::{Args, Command};
+use config::{Config, ConfigWatcher};
+use graphics::{ImageLayer, ImageProtocol};
+use ui::Overlays;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often a followed file is checked for new lines
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How often to check on a preview being generated in the background
+const BUSY_INTERVAL: Duration = Duration::from_millis(10);
 
 fn main() -> io::Result<()> {
-    // Parse optional path argument
-    let root_path = std::env::args()
-        .nth(1)
-        .map(|p| {
-            let path = PathBuf::from(&p);
-            if path.is_absolute() {
-                path
-            } else {
-                std::env::current_dir().unwrap_or_default().join(path)
-            }
-        })
-        .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+    let args = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Run(args)) => args,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(Command::Version) => {
+            println!("browse {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Err(e) => fail(&format!("{}\nTry 'browse --help' for more information.", e)),
+    };
 
-    let root_path = root_path.canonicalize().unwrap_or(root_path);
+    if let Some(config) = &args.config
+        && !config.is_file()
+    {
+        fail(&format!("config file '{}' not found", config.display()));
+    }
+    let mut config = match Config::load_or_default(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => fail(&e),
+    };
+    args.apply_to(&mut config);
+
+    if args.list_keys {
+        print!("{}", config.keymap.listing());
+        return Ok(());
+    }
+
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let path = match &args.path {
+        Some(p) => match cwd.join(p).canonicalize() {
+            Ok(path) => path,
+            Err(e) => fail(&format!("cannot access '{}': {}", p.display(), e)),
+        },
+        None => cwd.canonicalize().unwrap_or(cwd),
+    };
+
+    // A file argument roots the tree at its project (or parent) directory
+    // and reveals the file once the app is built
+    let (root_path, reveal) = if path.is_file() {
+        (tree::find_project_root(&path), Some(path))
+    } else {
+        (path, None)
+    };
+
+    // Ask the terminal for its background now if the theme depends on it:
+    // once the event loop reads input, the reply would arrive as key presses
+    if config.preview.theme == theme::AUTO {
+        theme::background();
+    }
+
+    let mut app = match App::new(root_path, config) {
+        Ok(app) => app,
+        Err(e) => fail(&e),
+    };
+    if let Some(depth) = args.depth {
+        app.expand_to_depth(depth);
+    }
+    if let Some(target) = reveal {
+        app.reveal(&target, args.line);
+    }
 
     // Terminal setup
     enable_raw_mode()?;
@@ -43,83 +125,87 @@ fn main() -> io::Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new(root_path);
+    let mut config_watcher = args
+        .config
+        .clone()
+        .or_else(config::default_path)
+        .map(ConfigWatcher::new);
+
+    let mut image_layer = ImageLayer::default();
 
     // Main loop
     loop {
-        let area_height = terminal.size()?.height;
+        let size = terminal.size()?;
+        let area_height = size.height;
+        app.tick(size.width, area_height);
 
-        terminal.draw(|f| ui::draw(f, &app))?;
+        let mut overlays = Overlays::default();
+        let mut link_cells = Vec::new();
+        let frame = terminal.draw(|f| overlays = ui::draw(f, &app))?;
+        if app.config.preview.hyperlinks {
+            link_cells = hyperlink_cells(frame.buffer, &overlays.links);
+        }
+
+        // Graphics-protocol images are drawn outside of ratatui, on top of
+        // the space it left blank for them
+        let placement = overlays.image.and_then(|area| app.image_placement(area));
+        if image_layer.clear_stale(terminal.backend_mut(), placement.as_ref())? {
+            terminal.clear()?;
+            terminal.draw(|f| {
+                ui::draw(f, &app);
+            })?;
+        }
+        image_layer.show(terminal.backend_mut(), placement)?;
+        draw_hyperlinks(terminal.backend_mut(), &link_cells)?;
 
         if app.should_quit {
             break;
         }
 
-        match event::read()? {
-            Event::Key(key) => match (key.code, key.modifiers) {
-                (KeyCode::Char('q'), _) | (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
-                    app.should_quit = true;
-                }
-                (KeyCode::Char('j'), _) | (KeyCode::Down, _) => {
-                    app.move_down();
-                }
-                (KeyCode::Char('k'), _) | (KeyCode::Up, _) => {
-                    app.move_up();
-                }
-                (KeyCode::Char('l'), _) | (KeyCode::Right, _) | (KeyCode::Enter, _) => {
-                    app.toggle_expand();
-                }
-                (KeyCode::Char('h'), _) | (KeyCode::Left, _) => {
-                    app.collapse_or_parent();
-                }
-                (KeyCode::Char('g'), _) => {
-                    app.jump_top();
-                }
-                (KeyCode::Char('G'), _) => {
-                    app.jump_bottom();
-                }
-                (KeyCode::Char('.'), _) => {
-                    app.toggle_hidden();
-                }
-                (KeyCode::Char('r'), _) => {
-                    app.refresh();
-                }
-                (KeyCode::Char('y'), _) => {
-                    app.yank_path();
-                }
-                (KeyCode::Char('J'), _) => {
-                    app.scroll_preview_down(1);
-                }
-                (KeyCode::Char('K'), _) => {
-                    app.scroll_preview_up(1);
-                }
-                (KeyCode::Char('d'), _) => {
-                    let half = (area_height / 2) as usize;
-                    app.scroll_preview_down(half);
-                }
-                (KeyCode::Char('u'), _) => {
-                    let half = (area_height / 2) as usize;
-                    app.scroll_preview_up(half);
-                }
+        if let Some(watcher) = config_watcher.as_mut()
+            && watcher.changed()
+        {
+            reload_config(&mut app, &args, watcher.path());
+        }
+
+        // Wake up periodically so config changes (and new lines in a
+        // followed file) are noticed while idle
+        let interval = if app.is_busy() {
+            BUSY_INTERVAL
+        } else if app.follow.is_some() {
+            FOLLOW_INTERVAL
+        } else {
+            POLL_INTERVAL
+        };
+        if !event::poll(interval)? {
+            continue;
+        }
+
+        let event = event::read()?;
+        if matches!(event, Event::Key(_)) {
+            app.status_message = None;
+        }
+
+        match event {
+            Event::Key(key) => app.handle_key(key, area_height),
+            // Overlays take the mouse while they're open
+            Event::Mouse(_) if app.palette.is_some() => {}
+            Event::Mouse(mouse) if app.help.is_some() => match mouse.kind {
+                MouseEventKind::ScrollUp => app.scroll_help(false),
+                MouseEventKind::ScrollDown => app.scroll_help(true),
                 _ => {}
             },
             Event::Mouse(mouse) => {
-                let tree_width = terminal.size()?.width * 35 / 100;
+                let tree_width = app.tree_width(terminal.size()?.width);
                 match mouse.kind {
-                    MouseEventKind::Down(MouseButton::Left) => {
-                        if mouse.column < tree_width {
-                            app.click_tree(mouse.row, area_height);
-                        }
+                    MouseEventKind::Down(MouseButton::Left) if mouse.column < tree_width => {
+                        app.click_tree(mouse.row, area_height);
                     }
-                    MouseEventKind::ScrollUp => {
-                        if mouse.column >= tree_width {
-                            app.scroll_preview_up(3);
-                        }
+                    MouseEventKind::ScrollUp if mouse.column >= tree_width => {
+                        app.scroll_preview_up(app.config.preview.mouse_scroll_lines);
                     }
-                    MouseEventKind::ScrollDown => {
-                        if mouse.column >= tree_width {
-                            app.scroll_preview_down(3);
-                        }
+                    MouseEventKind::ScrollDown if mouse.column >= tree_width => {
+                        app.scroll_preview_down(app.config.preview.mouse_scroll_lines);
                     }
                     _ => {}
                 }
@@ -129,6 +215,9 @@ fn main() -> io::Result<()> {
     }
 
     // Restore terminal
+    if app.image_protocol == ImageProtocol::Kitty {
+        image_layer.clear_all(terminal.backend_mut())?;
+    }
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
@@ -139,3 +228,60 @@ fn main() -> io::Result<()> {
 
     Ok(())
 }
+
+/// A link's URL and the cells showing its text
+type Hyperlink = (String, Vec<(u16, u16, Cell)>);
+
+/// The cells of each link that points outside the tree, to be drawn again
+/// as a hyperlink
+fn hyperlink_cells(buffer: &Buffer, links: &[(Rect, String)]) -> Vec<Hyperlink> {
+    links
+        .iter()
+        .filter(|(_, url)| url.contains("://") || url.starts_with("mailto:"))
+        .map(|(rect, url)| {
+            let cells = rect
+                .positions()
+                .filter_map(|p| buffer.cell(p).map(|cell| (p.x, p.y, cell.clone())))
+                .collect();
+            (url.clone(), cells)
+        })
+        .collect()
+}
+
+/// Wrap links in OSC 8 hyperlinks. The escape sequences can't go into
+/// ratatui's buffer (they would count as text), so the cells it already drew
+/// are sent again between them.
+fn draw_hyperlinks(backend: &mut CrosstermBackend<io::Stdout>, links: &[Hyperlink]) -> io::Result<()> {
+    for (url, cells) in links {
+        // Control characters would end the sequence early
+        let url: String = url.chars().filter(|c| !c.is_control()).collect();
+        write!(backend, "\x1b]8;;{}\x1b\\", url)?;
+        backend.draw(cells.iter().map(|(x, y, cell)| (*x, *y, cell)))?;
+        write!(backend, "\x1b]8;;\x1b\\")?;
+    }
+    Backend::flush(backend)
+}
+
+/// Re-read the config file after it changes on disk, keeping the current
+/// settings (and reporting why) if the new file is invalid
+fn reload_config(app: &mut App, args: &Args, path: &Path) {
+    let loaded = if path.exists() {
+        Config::load(path)
+    } else {
+        Ok(Config::default())
+    };
+    let result = loaded.and_then(|mut config| {
+        args.apply_to(&mut config);
+        app.apply_config(config)
+    });
+    app.status_message = Some(match result {
+        Ok(()) => "Config reloaded".to_string(),
+        Err(e) => format!("Config error: {}", config::summarize_error(&e)),
+    });
+}
+
+/// Print an error and exit before the terminal is put into raw mode
+fn fail(msg: &str) -> ! {
+    eprintln!("browse: {}", msg);
+    std::process::exit(2);
+}
@@ -0,0 +1,911 @@
+use std::collections::HashMap;
+
+use pulldown_cmark::{Alignment, BlockQuoteKind, CodeBlockKind, Event, MetadataBlockKind, Options, Parser, Tag, TagEnd};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::worker::Job;
+
+const BOLD: Style = Style::new().add_modifier(Modifier::BOLD);
+const DIM: Style = Style::new().add_modifier(Modifier::DIM);
+const ITALIC: Style = Style::new().add_modifier(Modifier::ITALIC);
+const STRIKETHROUGH: Style = Style::new().add_modifier(Modifier::CROSSED_OUT);
+const BLUE: Style = Style::new().fg(Color::Blue);
+const LINK: Style = BLUE.add_modifier(Modifier::UNDERLINED);
+const INLINE_CODE: Style = Style::new().fg(Color::Cyan).add_modifier(Modifier::DIM);
+const CHECKED: Style = Style::new().fg(Color::Green);
+const HEADINGS: [Style; 4] = [
+    BOLD.fg(Color::Cyan),
+    BOLD.fg(Color::Green),
+    BOLD.fg(Color::Yellow),
+    BOLD.fg(Color::Magenta),
+];
+
+/// Code blocks are indented by this much
+const CODE_INDENT: &str = "  ";
+
+/// Text is never wrapped narrower than this, however deeply it's nested
+const MIN_WIDTH: usize = 20;
+
+/// Narrowest a table column is squeezed to
+const MIN_COLUMN: usize = 3;
+
+/// Highlights a code block's text for its language (the fence's info
+/// string); None leaves the block plain
+pub type Highlight<'a> = dyn Fn(&str, &str) -> Option<Vec<Line<'static>>> + 'a;
+
+/// A rendered markdown document
+pub struct Document {
+    pub lines: Vec<Line<'static>>,
+    /// Links in the order they appear
+    pub links: Vec<Link>,
+    pub headings: Vec<Heading>,
+}
+
+pub struct Link {
+    pub url: String,
+    /// Where the link text is drawn; more than one place if it wraps
+    pub regions: Vec<Region>,
+}
+
+/// A run of columns on one rendered line
+#[derive(Clone, Copy)]
+pub struct Region {
+    pub line: usize,
+    pub column: usize,
+    pub width: usize,
+}
+
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+    /// The fragment that links to it, as GitHub generates it
+    pub anchor: String,
+    /// First rendered line of the heading
+    pub line: usize,
+}
+
+impl Document {
+    /// The heading `#anchor` refers to
+    pub fn heading(&self, anchor: &str) -> Option<&Heading> {
+        let anchor = anchor.to_lowercase();
+        self.headings.iter().find(|h| h.anchor == anchor)
+    }
+
+    /// The headings enclosing `line`, outermost first
+    pub fn section(&self, line: usize) -> Vec<&Heading> {
+        let mut path: Vec<&Heading> = Vec::new();
+        for heading in self.headings.iter().take_while(|h| h.line <= line) {
+            path.retain(|h| h.level < heading.level);
+            path.push(heading);
+        }
+        path
+    }
+}
+
+/// Render CommonMark with the GitHub extensions (tables, task lists,
+/// footnotes, strikethrough), wrapping text to `width` columns
+pub fn render(input: &str, width: u16, job: &Job, highlight: &Highlight) -> Document {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_GFM
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+    let mut renderer = Renderer::new(width as usize, highlight);
+    for (i, event) in Parser::new_ext(input, options).enumerate() {
+        if i % 256 == 0 && job.is_cancelled() {
+            break;
+        }
+        renderer.event(event);
+    }
+    renderer.finish()
+}
+
+/// Inline text, with the index of the link it belongs to
+type Piece = (Span<'static>, Option<usize>);
+
+/// Rendered lines, with where links and headings ended up in them
+#[derive(Default)]
+struct Output {
+    lines: Vec<Line<'static>>,
+    /// Link index and position of each piece of link text
+    regions: Vec<(usize, Region)>,
+    headings: Vec<Heading>,
+}
+
+/// A blockquote or list item: the prefix its lines are drawn with
+struct Container {
+    /// Prefix of the container's first line (a list item's bullet)
+    first: Vec<Span<'static>>,
+    /// Prefix of the lines after it
+    rest: Vec<Span<'static>>,
+    started: bool,
+}
+
+struct CodeBlock {
+    lang: String,
+    text: String,
+}
+
+struct Table {
+    alignments: Vec<Alignment>,
+    /// Cells of each row, the header first
+    rows: Vec<Vec<Vec<Piece>>>,
+    row: Vec<Vec<Piece>>,
+}
+
+/// A footnote definition being rendered, with the document output set
+/// aside until it ends
+struct Footnote {
+    number: usize,
+    outer: Output,
+    outer_gap: bool,
+}
+
+struct Renderer<'a> {
+    width: usize,
+    highlight: &'a Highlight<'a>,
+    out: Output,
+    /// Inline content of the current block, not yet wrapped into lines
+    spans: Vec<Piece>,
+    /// Styles of the open inline elements, innermost last
+    styles: Vec<Style>,
+    /// Style of the current block's text (a heading's)
+    block_style: Style,
+    links: Vec<Link>,
+    /// Index of the link being rendered
+    link: Option<usize>,
+    /// How many headings had each anchor, to number repeats
+    anchors: HashMap<String, usize>,
+    /// Open blockquotes and list items, outermost first
+    containers: Vec<Container>,
+    /// Next number of each open list, None for bullet lists
+    lists: Vec<Option<u64>>,
+    /// A blank line goes before the next block
+    gap: bool,
+    code: Option<CodeBlock>,
+    table: Option<Table>,
+    /// Footnote labels, numbered in order of first use
+    footnote_labels: Vec<String>,
+    footnote: Option<Footnote>,
+    /// Rendered footnote definitions, listed after the document
+    footnotes: Vec<(usize, Output)>,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(width: usize, highlight: &'a Highlight<'a>) -> Self {
+        Self {
+            width,
+            highlight,
+            out: Output::default(),
+            spans: Vec::new(),
+            styles: Vec::new(),
+            block_style: Style::new(),
+            links: Vec::new(),
+            link: None,
+            anchors: HashMap::new(),
+            containers: Vec::new(),
+            lists: Vec::new(),
+            gap: false,
+            code: None,
+            table: None,
+            footnote_labels: Vec::new(),
+            footnote: None,
+            footnotes: Vec::new(),
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => match &mut self.code {
+                Some(code) => code.text.push_str(&text),
+                None => self.text(&text, self.style()),
+            },
+            Event::Code(text) => self.text(&text, self.style().patch(INLINE_CODE)),
+            Event::InlineMath(text) | Event::DisplayMath(text) => self.text(&text, self.style()),
+            Event::Html(html) => {
+                for line in html.lines() {
+                    self.push_line(vec![(Span::styled(line.to_string(), DIM), None)]);
+                }
+            }
+            Event::InlineHtml(html) => {
+                let tag = html.trim().to_ascii_lowercase();
+                if tag.starts_with("<br") {
+                    self.flush();
+                } else {
+                    self.text(&html, DIM);
+                }
+            }
+            Event::FootnoteReference(label) => {
+                let number = self.footnote_number(&label);
+                self.text(&format!("[{}]", number), BLUE);
+            }
+            Event::SoftBreak => self.text(" ", self.style()),
+            Event::HardBreak => self.flush(),
+            Event::Rule => {
+                self.start_block();
+                let width = self.text_width();
+                self.push_line(vec![(Span::styled("─".repeat(width), DIM), None)]);
+                self.gap = true;
+            }
+            Event::TaskListMarker(checked) => self.task_marker(checked),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.start_block(),
+            Tag::Heading { level, .. } => {
+                self.start_block();
+                self.block_style = HEADINGS[(level as usize - 1).min(HEADINGS.len() - 1)];
+            }
+            Tag::BlockQuote(kind) => {
+                self.start_block();
+                let bar = vec![Span::styled("│ ", DIM)];
+                self.containers.push(Container {
+                    first: bar.clone(),
+                    rest: bar,
+                    started: false,
+                });
+                if let Some(kind) = kind {
+                    let (label, color) = alert(kind);
+                    self.push_line(vec![(Span::styled(label, BOLD.fg(color)), None)]);
+                }
+            }
+            Tag::CodeBlock(kind) => {
+                self.start_block();
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().unwrap_or("").to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some(CodeBlock {
+                    lang,
+                    text: String::new(),
+                });
+            }
+            Tag::MetadataBlock(kind) => {
+                self.start_block();
+                let lang = match kind {
+                    MetadataBlockKind::YamlStyle => "yaml",
+                    MetadataBlockKind::PlusesStyle => "toml",
+                };
+                self.code = Some(CodeBlock {
+                    lang: lang.to_string(),
+                    text: String::new(),
+                });
+            }
+            Tag::HtmlBlock => self.start_block(),
+            Tag::List(start) => {
+                self.start_block();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.start_block();
+                // Top-level lists are indented a little, nested ones line up
+                // under their parent item's text
+                let indent = if self.lists.len() <= 1 { "  " } else { "" };
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                let width = indent.width() + marker.width();
+                self.containers.push(Container {
+                    first: vec![Span::raw(indent), Span::styled(marker, BLUE)],
+                    rest: vec![Span::raw(" ".repeat(width))],
+                    started: false,
+                });
+            }
+            Tag::FootnoteDefinition(label) => {
+                self.flush();
+                let number = self.footnote_number(&label);
+                self.footnote = Some(Footnote {
+                    number,
+                    outer: std::mem::take(&mut self.out),
+                    outer_gap: self.gap,
+                });
+                self.gap = false;
+                let marker = format!("[{}] ", number);
+                let width = marker.width();
+                self.containers.push(Container {
+                    first: vec![Span::styled(marker, BLUE)],
+                    rest: vec![Span::raw(" ".repeat(width))],
+                    started: false,
+                });
+            }
+            Tag::Table(alignments) => {
+                self.start_block();
+                self.table = Some(Table {
+                    alignments,
+                    rows: Vec::new(),
+                    row: Vec::new(),
+                });
+            }
+            Tag::TableHead => self.styles.push(BOLD),
+            Tag::TableRow | Tag::TableCell => {}
+            Tag::Emphasis => self.styles.push(ITALIC),
+            Tag::Strong => self.styles.push(BOLD),
+            Tag::Strikethrough => self.styles.push(STRIKETHROUGH),
+            Tag::Superscript | Tag::Subscript => self.styles.push(Style::new()),
+            Tag::Link { dest_url, .. } => {
+                self.link = Some(self.links.len());
+                self.links.push(Link {
+                    url: dest_url.to_string(),
+                    regions: Vec::new(),
+                });
+                self.styles.push(LINK);
+            }
+            Tag::Image { .. } => {
+                self.text("[image: ", DIM);
+                self.styles.push(DIM);
+            }
+            Tag::DefinitionList | Tag::DefinitionListTitle | Tag::DefinitionListDefinition => self.start_block(),
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph | TagEnd::DefinitionListTitle | TagEnd::DefinitionListDefinition => {
+                self.flush();
+                self.gap = true;
+            }
+            TagEnd::Heading(level) => {
+                let text: String = self.spans.iter().map(|(span, _)| span.content.as_ref()).collect();
+                let anchor = self.anchor(&text);
+                self.out.headings.push(Heading {
+                    level: level as u8,
+                    text,
+                    anchor,
+                    line: self.out.lines.len(),
+                });
+                self.flush();
+                self.block_style = Style::new();
+                self.gap = true;
+            }
+            TagEnd::BlockQuote(_) | TagEnd::Item => {
+                self.flush();
+                self.containers.pop();
+                if tag != TagEnd::Item {
+                    self.gap = true;
+                }
+            }
+            TagEnd::CodeBlock | TagEnd::MetadataBlock(_) => {
+                if let Some(code) = self.code.take() {
+                    self.code_block(&code);
+                }
+                self.gap = true;
+            }
+            TagEnd::HtmlBlock => self.gap = true,
+            TagEnd::List(_) => {
+                self.flush();
+                self.lists.pop();
+                // Nested lists continue their parent item without a gap
+                if self.lists.is_empty() {
+                    self.gap = true;
+                }
+            }
+            TagEnd::FootnoteDefinition => {
+                self.flush();
+                self.containers.pop();
+                if let Some(footnote) = self.footnote.take() {
+                    let output = std::mem::replace(&mut self.out, footnote.outer);
+                    self.footnotes.push((footnote.number, output));
+                    self.gap = footnote.outer_gap;
+                }
+            }
+            TagEnd::TableCell => {
+                let cell = std::mem::take(&mut self.spans);
+                if let Some(table) = &mut self.table {
+                    table.row.push(cell);
+                }
+            }
+            TagEnd::TableHead | TagEnd::TableRow => {
+                if tag == TagEnd::TableHead {
+                    self.styles.pop();
+                }
+                if let Some(table) = &mut self.table {
+                    let row = std::mem::take(&mut table.row);
+                    table.rows.push(row);
+                }
+            }
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    self.table(table);
+                }
+                self.gap = true;
+            }
+            TagEnd::Image => {
+                self.styles.pop();
+                self.text("]", DIM);
+            }
+            TagEnd::Emphasis
+            | TagEnd::Strong
+            | TagEnd::Strikethrough
+            | TagEnd::Superscript
+            | TagEnd::Subscript => {
+                self.styles.pop();
+            }
+            TagEnd::Link => {
+                self.styles.pop();
+                self.link = None;
+            }
+            TagEnd::DefinitionList => self.gap = true,
+        }
+    }
+
+    /// The document, with footnotes listed at the end
+    fn finish(mut self) -> Document {
+        self.flush();
+        let mut out = self.out;
+        if !self.footnotes.is_empty() {
+            self.footnotes.sort_by_key(|(number, _)| *number);
+            out.lines.push(Line::default());
+            out.lines.push(Line::from(Span::styled("─".repeat(MIN_WIDTH), DIM)));
+            for (_, footnote) in self.footnotes {
+                let offset = out.lines.len();
+                out.lines.extend(footnote.lines);
+                out.regions.extend(footnote.regions.into_iter().map(|(link, region)| {
+                    let line = region.line + offset;
+                    (link, Region { line, ..region })
+                }));
+                out.headings.extend(footnote.headings.into_iter().map(|heading| {
+                    let line = heading.line + offset;
+                    Heading { line, ..heading }
+                }));
+            }
+        }
+
+        let mut links = self.links;
+        for (link, region) in out.regions {
+            links[link].regions.push(region);
+        }
+        Document {
+            lines: out.lines,
+            links,
+            headings: out.headings,
+        }
+    }
+
+    /// GitHub's anchor for a heading: lowercased, punctuation dropped,
+    /// spaces turned into dashes, and numbered if it repeats
+    fn anchor(&mut self, text: &str) -> String {
+        let slug: String = text
+            .trim()
+            .to_lowercase()
+            .chars()
+            .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+            .map(|c| if c == ' ' { '-' } else { c })
+            .collect();
+        let count = self.anchors.entry(slug.clone()).or_insert(0);
+        *count += 1;
+        match *count {
+            1 => slug,
+            n => format!("{}-{}", slug, n - 1),
+        }
+    }
+
+    /// Style for text at the current position
+    fn style(&self) -> Style {
+        self.styles
+            .iter()
+            .fold(self.block_style, |style, &inner| style.patch(inner))
+    }
+
+    fn text(&mut self, text: &str, style: Style) {
+        push_piece(&mut self.spans, text, style, self.link);
+    }
+
+    /// Columns left for text inside the open containers
+    fn text_width(&self) -> usize {
+        let prefix: usize = self
+            .containers
+            .iter()
+            .flat_map(|c| &c.first)
+            .map(|span| span.content.width())
+            .sum();
+        self.width.saturating_sub(prefix).max(MIN_WIDTH)
+    }
+
+    /// End the current block's text, and leave a blank line before the
+    /// next block if one is due
+    fn start_block(&mut self) {
+        self.flush();
+        if self.gap && !self.out.lines.is_empty() {
+            // Containers that haven't drawn their first line yet (a list
+            // item's bullet) are left blank
+            let spans: Vec<Span<'static>> = self
+                .containers
+                .iter()
+                .flat_map(|c| if c.started { c.rest.clone() } else { vec![Span::raw(" ".repeat(width(&c.first)))] })
+                .collect();
+            let mut line = Line::from(spans);
+            trim_end(&mut line);
+            self.out.lines.push(line);
+        }
+        self.gap = false;
+    }
+
+    /// Wrap the pending inline text into lines
+    fn flush(&mut self) {
+        if self.spans.is_empty() {
+            return;
+        }
+        let spans = std::mem::take(&mut self.spans);
+        for line in wrap(spans, self.text_width()) {
+            self.push_line(line);
+        }
+    }
+
+    /// Add a line inside the open containers
+    fn push_line(&mut self, pieces: Vec<Piece>) {
+        let mut line: Vec<Span<'static>> = Vec::new();
+        for container in &mut self.containers {
+            if container.started {
+                line.extend(container.rest.iter().cloned());
+            } else {
+                line.extend(container.first.iter().cloned());
+                container.started = true;
+            }
+        }
+
+        let number = self.out.lines.len();
+        let mut column = width(&line);
+        for (span, link) in pieces {
+            let span_width = span.content.width();
+            if let Some(link) = link {
+                match self.out.regions.last_mut() {
+                    Some((last, region))
+                        if *last == link && region.line == number && region.column + region.width == column =>
+                    {
+                        region.width += span_width;
+                    }
+                    _ => self.out.regions.push((
+                        link,
+                        Region {
+                            line: number,
+                            column,
+                            width: span_width,
+                        },
+                    )),
+                }
+            }
+            column += span_width;
+            line.push(span);
+        }
+        self.out.lines.push(Line::from(line));
+    }
+
+    /// Replace the bullet of the current list item with a checkbox
+    fn task_marker(&mut self, checked: bool) {
+        let Some(item) = self.containers.last_mut().filter(|c| !c.started) else {
+            return;
+        };
+        let Some(marker) = item.first.last_mut() else {
+            return;
+        };
+        let (checkbox, style) = if checked { ("☑", CHECKED) } else { ("☐", BLUE) };
+        let padding = marker.content.width().saturating_sub(checkbox.width()).max(1);
+        *marker = Span::styled(format!("{}{}", checkbox, " ".repeat(padding)), style);
+    }
+
+    fn footnote_number(&mut self, label: &str) -> usize {
+        match self.footnote_labels.iter().position(|l| l == label) {
+            Some(i) => i + 1,
+            None => {
+                self.footnote_labels.push(label.to_string());
+                self.footnote_labels.len()
+            }
+        }
+    }
+
+    fn code_block(&mut self, code: &CodeBlock) {
+        let highlighted = if code.lang.is_empty() {
+            None
+        } else {
+            (self.highlight)(&code.lang, &code.text)
+        };
+        let lines = highlighted.unwrap_or_else(|| {
+            code.text
+                .lines()
+                .map(|line| Line::from(Span::styled(line.to_string(), DIM)))
+                .collect()
+        });
+        for line in lines {
+            let mut pieces = vec![(Span::raw(CODE_INDENT), None)];
+            pieces.extend(line.spans.into_iter().map(|span| (span, None)));
+            self.push_line(pieces);
+        }
+    }
+
+    /// Lay out a table with box-drawing borders, narrowing the widest
+    /// columns (and wrapping their cells) when it doesn't fit
+    fn table(&mut self, table: Table) {
+        let columns = table
+            .alignments
+            .len()
+            .max(table.rows.iter().map(Vec::len).max().unwrap_or(0));
+        if columns == 0 {
+            return;
+        }
+        let mut widths = vec![MIN_COLUMN; columns];
+        for row in &table.rows {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(pieces_width(cell));
+            }
+        }
+        let borders = 3 * columns + 1;
+        let available = self.text_width().saturating_sub(borders);
+        while widths.iter().sum::<usize>() > available {
+            let (widest, &width) = widths
+                .iter()
+                .enumerate()
+                .max_by_key(|&(_, w)| *w)
+                .unwrap_or((0, &0));
+            if width <= MIN_COLUMN {
+                break;
+            }
+            widths[widest] -= 1;
+        }
+
+        let border = |left: &str, middle: &str, right: &str| {
+            let segments: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+            vec![(Span::styled(format!("{}{}{}", left, segments.join(middle), right), DIM), None)]
+        };
+        self.push_line(border("┌", "┬", "┐"));
+        for (index, row) in table.rows.into_iter().enumerate() {
+            if index == 1 {
+                self.push_line(border("├", "┼", "┤"));
+            }
+            let mut cells: Vec<Vec<Vec<Piece>>> = row
+                .into_iter()
+                .zip(&widths)
+                .map(|(cell, &width)| wrap(cell, width))
+                .collect();
+            cells.resize(columns, Vec::new());
+            let height = cells.iter().map(Vec::len).max().unwrap_or(0).max(1);
+            for line in 0..height {
+                let mut pieces = vec![(Span::styled("│", DIM), None)];
+                for (column, cell) in cells.iter_mut().enumerate() {
+                    let content = cell.get_mut(line).map(std::mem::take).unwrap_or_default();
+                    let slack = widths[column].saturating_sub(pieces_width(&content));
+                    let alignment = table.alignments.get(column).copied().unwrap_or(Alignment::None);
+                    let (left, right) = match alignment {
+                        Alignment::Right => (slack, 0),
+                        Alignment::Center => (slack / 2, slack - slack / 2),
+                        Alignment::Left | Alignment::None => (0, slack),
+                    };
+                    pieces.push((Span::raw(" ".repeat(left + 1)), None));
+                    pieces.extend(content);
+                    pieces.push((Span::raw(" ".repeat(right + 1)), None));
+                    pieces.push((Span::styled("│", DIM), None));
+                }
+                self.push_line(pieces);
+            }
+        }
+        self.push_line(border("└", "┴", "┘"));
+    }
+}
+
+/// Heading line of a GitHub alert (`> [!NOTE]`)
+fn alert(kind: BlockQuoteKind) -> (&'static str, Color) {
+    match kind {
+        BlockQuoteKind::Note => ("Note", Color::Blue),
+        BlockQuoteKind::Tip => ("Tip", Color::Green),
+        BlockQuoteKind::Important => ("Important", Color::Magenta),
+        BlockQuoteKind::Warning => ("Warning", Color::Yellow),
+        BlockQuoteKind::Caution => ("Caution", Color::Red),
+    }
+}
+
+fn width(spans: &[Span]) -> usize {
+    spans.iter().map(|span| span.content.width()).sum()
+}
+
+fn pieces_width(pieces: &[Piece]) -> usize {
+    pieces.iter().map(|(span, _)| span.content.width()).sum()
+}
+
+/// Append text, extending the last piece if it has the same style and link
+fn push_piece(pieces: &mut Vec<Piece>, text: &str, style: Style, link: Option<usize>) {
+    match pieces.last_mut() {
+        Some((last, last_link)) if last.style == style && *last_link == link => {
+            last.content.to_mut().push_str(text)
+        }
+        _ => pieces.push((Span::styled(text.to_string(), style), link)),
+    }
+}
+
+fn trim_end(line: &mut Line) {
+    while let Some(last) = line.spans.last_mut() {
+        let trimmed = last.content.trim_end().len();
+        if trimmed > 0 {
+            last.content.to_mut().truncate(trimmed);
+            break;
+        }
+        line.spans.pop();
+    }
+}
+
+/// Break styled text into lines at most `width` columns wide, at spaces
+/// where possible. Runs of whitespace collapse into one space.
+fn wrap(pieces: Vec<Piece>, width: usize) -> Vec<Vec<Piece>> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line: Vec<Piece> = Vec::new();
+    let mut line_width = 0;
+    // Whitespace before the next word, dropped if the word starts a line
+    let mut space: Option<(Style, Option<usize>)> = None;
+
+    for (span, link) in &pieces {
+        let (style, link) = (span.style, *link);
+        for word in words(&span.content) {
+            if word.starts_with(char::is_whitespace) {
+                if !line.is_empty() {
+                    space = Some((style, link));
+                }
+                continue;
+            }
+            let mut word_width = word.width();
+            let space_width = usize::from(space.is_some());
+            if !line.is_empty() && line_width + space_width + word_width > width {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+                space = None;
+            }
+            if let Some((style, link)) = space.take() {
+                push_piece(&mut line, " ", style, link);
+                line_width += 1;
+            }
+
+            // A word longer than a line is split wherever it runs out of room
+            let mut rest = word;
+            while line_width + word_width > width {
+                let mut split = 0;
+                let mut split_width = 0;
+                for (i, c) in rest.char_indices() {
+                    let w = c.width().unwrap_or(0);
+                    if line_width + split_width + w > width {
+                        break;
+                    }
+                    split = i + c.len_utf8();
+                    split_width += w;
+                }
+                if split == 0 && line.is_empty() {
+                    // Not even one character fits; take it anyway
+                    split = rest.chars().next().map_or(rest.len(), char::len_utf8);
+                }
+                push_piece(&mut line, &rest[..split], style, link);
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+                rest = &rest[split..];
+                word_width = rest.width();
+            }
+            if !rest.is_empty() {
+                push_piece(&mut line, rest, style, link);
+                line_width += word_width;
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Split text into alternating runs of whitespace and non-whitespace
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let end = rest
+            .char_indices()
+            .find(|(_, c)| c.is_whitespace() != first.is_whitespace())
+            .map_or(rest.len(), |(i, _)| i);
+        let (word, tail) = rest.split_at(end);
+        rest = tail;
+        Some(word)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(input: &str, width: u16) -> Document {
+        render(input, width, &Job::standalone(), &|_, _| None)
+    }
+
+    fn text(input: &str, width: u16) -> Vec<String> {
+        document(input, width)
+            .lines
+            .iter()
+            .map(|line| line.spans.iter().map(|span| span.content.as_ref()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn wraps_text_at_the_width() {
+        let lines = text(
+            "Some words that go on and on well past the edge of a narrow pane, \
+             unbreakable-supercalifragilisticexpialidocious too.",
+            30,
+        );
+        assert_eq!(
+            lines,
+            [
+                "Some words that go on and on",
+                "well past the edge of a narrow",
+                "pane,",
+                "unbreakable-supercalifragilist",
+                "icexpialidocious too.",
+            ]
+        );
+        // Never narrower than MIN_WIDTH
+        assert!(text("one two three four five six", 5).iter().all(|l| l.width() <= MIN_WIDTH));
+    }
+
+    #[test]
+    fn tables_are_aligned_and_squeezed_to_fit() {
+        let lines = text("| name | n |\n|:---|--:|\n| ada | 36 |\n| a much longer cell here | 1 |\n", 30);
+        assert_eq!(
+            lines,
+            [
+                "┌──────────────────────┬─────┐",
+                "│ name                 │   n │",
+                "├──────────────────────┼─────┤",
+                "│ ada                  │  36 │",
+                "│ a much longer cell   │   1 │",
+                "│ here                 │     │",
+                "└──────────────────────┴─────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn task_list_items_get_checkboxes() {
+        assert_eq!(text("- [x] done\n- [ ] todo\n- plain\n", 40), ["  ☑ done", "  ☐ todo", "  • plain"]);
+    }
+
+    #[test]
+    fn footnotes_are_numbered_by_use_and_listed_last() {
+        let lines = text("Text[^n] and more[^m].\n\n[^m]: Second.\n\n[^n]: First.\n\nAfter.\n", 40);
+        assert_eq!(
+            lines,
+            [
+                "Text[1] and more[2].",
+                "",
+                "After.",
+                "",
+                "─".repeat(MIN_WIDTH).as_str(),
+                "[1] First.",
+                "[2] Second.",
+            ]
+        );
+    }
+
+    #[test]
+    fn collects_links_and_heading_anchors() {
+        let document = document(
+            "# Intro\n\nSee [a long link text](#intro) and [b](b.md).\n\n## Intro\n\n### Setup & Use\n",
+            20,
+        );
+        let urls: Vec<&str> = document.links.iter().map(|l| l.url.as_str()).collect();
+        assert_eq!(urls, ["#intro", "b.md"]);
+        let region = document.links[0].regions[0];
+        assert_eq!((region.line, region.column, region.width), (2, 4, 16));
+        let region = document.links[1].regions[0];
+        assert_eq!((region.line, region.column, region.width), (3, 4, 1));
+
+        let anchors: Vec<&str> = document.headings.iter().map(|h| h.anchor.as_str()).collect();
+        assert_eq!(anchors, ["intro", "intro-1", "setup--use"]);
+        assert_eq!(document.heading("Intro-1").map(|h| h.line), Some(5));
+        let section: Vec<&str> = document.section(7).iter().map(|h| h.anchor.as_str()).collect();
+        assert_eq!(section, ["intro", "intro-1", "setup--use"]);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::fuzzy::fuzzy_match;
+use crate::keymap::Action;
+use crate::tree::SortMode;
+
+/// What a palette entry does when chosen
+#[derive(Clone)]
+pub enum PaletteCommand {
+    Action(Action),
+    Sort(SortMode),
+    Theme(String),
+    Heading(usize), // line of a markdown heading
+}
+
+pub struct PaletteEntry {
+    pub label: String,
+    pub keys: String, // bound keys for display, empty if unbound
+    pub command: PaletteCommand,
+    pub active: bool, // the current sort mode, theme or section
+}
+
+pub struct PaletteMatch<'a> {
+    pub entry: &'a PaletteEntry,
+    pub positions: Vec<usize>, // char indices of `label` matching the query
+}
+
+/// State of the command palette: a fuzzy-filtered list of entries
+pub struct PaletteState {
+    pub query: String,
+    pub selected: usize, // index into `matches()`
+    pub original_theme: Option<String>, // theme picker only: restored on Esc
+    pub original_scroll: Option<usize>, // outline only: restored on Esc
+    entries: Vec<PaletteEntry>,
+}
+
+impl PaletteState {
+    pub fn new(entries: Vec<PaletteEntry>) -> Self {
+        Self {
+            query: String::new(),
+            selected: 0,
+            original_theme: None,
+            original_scroll: None,
+            entries,
+        }
+    }
+
+    /// A palette listing only themes, starting at the active one. Moving
+    /// the selection previews each theme; Esc goes back to `original`.
+    pub fn theme_picker(entries: Vec<PaletteEntry>, original: String) -> Self {
+        let selected = entries.iter().position(|e| e.active).unwrap_or(0);
+        Self {
+            selected,
+            original_theme: Some(original),
+            ..Self::new(entries)
+        }
+    }
+
+    pub fn is_theme_picker(&self) -> bool {
+        self.original_theme.is_some()
+    }
+
+    /// A palette listing the headings of a markdown preview, starting at the
+    /// current section. Moving the selection scrolls the preview to each
+    /// heading; Esc goes back to `original`.
+    pub fn outline(entries: Vec<PaletteEntry>, original: usize) -> Self {
+        let selected = entries.iter().position(|e| e.active).unwrap_or(0);
+        Self {
+            selected,
+            original_scroll: Some(original),
+            ..Self::new(entries)
+        }
+    }
+
+    pub fn is_outline(&self) -> bool {
+        self.original_scroll.is_some()
+    }
+
+    /// Entries matching the query, best first. With an empty query every
+    /// entry is listed in its original order.
+    pub fn matches(&self) -> Vec<PaletteMatch<'_>> {
+        if self.query.is_empty() {
+            return self
+                .entries
+                .iter()
+                .map(|entry| PaletteMatch {
+                    entry,
+                    positions: Vec::new(),
+                })
+                .collect();
+        }
+
+        let mut scored: Vec<(i64, usize, PaletteMatch)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| {
+                let (score, positions) = fuzzy_match(&self.query, &entry.label)?;
+                Some((score, i, PaletteMatch { entry, positions }))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        scored.into_iter().map(|(_, _, m)| m).collect()
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn selected_command(&self) -> Option<PaletteCommand> {
+        self.matches()
+            .get(self.selected)
+            .map(|m| m.entry.command.clone())
+    }
+
+    pub fn move_selection(&mut self, down: bool) {
+        let count = self.matches().len();
+        if count == 0 {
+            return;
+        }
+        self.selected = if down {
+            (self.selected + 1) % count
+        } else {
+            (self.selected + count - 1) % count
+        };
+    }
+
+    pub fn set_query(&mut self, query: String) {
+        self.query = query;
+        self.selected = 0;
+    }
+}
@@ -1,278 +1,476 @@
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use syntect::easy::HighlightLines;
-use syntect::highlighting::ThemeSet;
-use syntect::parsing::SyntaxSet;
-use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
-
-// ANSI escape helpers
-const RESET: &str = "\x1b[0m";
-const BOLD: &str = "\x1b[1m";
-const DIM: &str = "\x1b[2m";
-const ITALIC: &str = "\x1b[3m";
-const CYAN: &str = "\x1b[36m";
-const YELLOW: &str = "\x1b[33m";
-const GREEN: &str = "\x1b[32m";
-const MAGENTA: &str = "\x1b[35m";
-const BLUE: &str = "\x1b[34m";
-
-const MAX_PREVIEW_BYTES: u64 = 512 * 1024; // 512 KB
-
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use image::RgbaImage;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use syntect::util::LinesWithEndings;
+
+use crate::ansi::parse_ansi_line;
+use crate::archive::{self, ArchiveKind, Entry};
+use crate::binary;
+use crate::config::SyntaxConfig;
+use crate::elf::{self, ElfInfo};
+use crate::graphics;
+use crate::highlight::{self, Highlighted};
+use crate::markdown::{self, Document};
+use crate::sqlite::{self, Database};
+use crate::stream::LineIndex;
+use crate::structured::{self, Tree};
+use crate::table::{self, Table};
+use crate::theme;
+use crate::vfs::Vfs;
+use crate::worker::Job;
+
+// Styles for archive listings
+const BOLD: Style = Style::new().add_modifier(Modifier::BOLD);
+const DIM: Style = Style::new().add_modifier(Modifier::DIM);
+const BLUE: Style = Style::new().fg(Color::Blue);
+
+/// Larger images are shown as a hex dump rather than decoded
+const MAX_IMAGE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Larger executables are shown as a hex dump rather than parsed
+const MAX_ELF_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Binary kinds (as named by `binary::sniff`) previewed as images
+const IMAGE_KINDS: &[&str] = &["PNG image", "JPEG image", "GIF image", "WebP image", "BMP image", "ICO image"];
+
+#[derive(Clone)]
 pub enum PreviewContent {
-    Text(String),
+    /// Styled lines, rendered once when the preview is generated
+    Text(Vec<Line<'static>>),
+    /// Source code, highlighted as it's scrolled into view
+    Code(Arc<Highlighted>),
+    /// Rendered markdown, with its links and headings
+    Markdown(Arc<Document>),
+    /// JSON, YAML or TOML as a foldable tree
+    Structured(Arc<Tree>),
+    /// CSV or TSV as aligned columns
+    Table(Arc<Table>),
+    /// SQLite schema listing; its tables' rows are read as they're viewed
+    Database(Arc<Database>),
+    /// Summary of an ELF executable, library or object file
+    Elf(Arc<ElfInfo>),
     Directory(String),
-    Binary(String),
+    /// Binary file shown as a hex dump, read lazily as it's scrolled
+    Hex {
+        path: PathBuf,
+        size: u64,
+        header: String,
+        /// The start of a file that can't be read from an offset cheaply,
+        /// read once and dumped from memory
+        bytes: Option<Arc<[u8]>>,
+    },
+    /// Text file too large to read in full, read around the scroll position
+    Stream(Arc<LineIndex>),
+    /// Decoded image, drawn with the configured graphics protocol
+    Image {
+        path: PathBuf,
+        header: String,
+        image: RgbaImage,
+    },
+    /// The preview is still being generated
+    Loading,
     Empty,
     Error(String),
 }
 
+/// Cheap to clone (the syntax and theme sets are shared), so each preview
+/// request can take a copy with the current settings
+#[derive(Clone)]
 pub struct Previewer {
-    syntax_set: SyntaxSet,
-    theme_set: ThemeSet,
+    syntax_set: Arc<SyntaxSet>,
+    mappings: Arc<SyntaxConfig>,
+    theme_set: Arc<ThemeSet>,
+    theme_name: String,
+    max_bytes: u64,
+    /// Columns available in the preview pane
+    width: u16,
 }
 
 impl Previewer {
-    pub fn new() -> Self {
+    pub fn new(syntax_set: SyntaxSet) -> Self {
         Self {
-            syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set: ThemeSet::load_defaults(),
+            syntax_set: Arc::new(syntax_set),
+            mappings: Arc::new(SyntaxConfig::default()),
+            theme_set: Arc::new(ThemeSet::load_defaults()),
+            theme_name: theme::DEFAULT_DARK.to_string(),
+            max_bytes: 512 * 1024,
+            width: 80,
         }
     }
 
-    pub fn preview(&self, file_path: &Path) -> (PreviewContent, usize) {
-        let metadata = match fs::metadata(file_path) {
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Larger text files are streamed rather than read in full
+    pub fn set_max_bytes(&mut self, max_bytes: u64) {
+        self.max_bytes = max_bytes;
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn set_width(&mut self, width: u16) {
+        self.width = width;
+    }
+
+    /// Use `mappings` to pick syntaxes, failing if one names a syntax that
+    /// doesn't exist. Returns whether anything changed.
+    pub fn set_syntax_mappings(&mut self, mappings: &SyntaxConfig) -> Result<bool, String> {
+        let entries = mappings.extensions.iter().chain(&mappings.filenames);
+        for (file, name) in entries {
+            if self.syntax_by_name(name).is_none() {
+                return Err(format!("unknown syntax '{}' for '{}'", name, file));
+            }
+        }
+        if *self.mappings == *mappings {
+            return Ok(false);
+        }
+        self.mappings = Arc::new(mappings.clone());
+        Ok(true)
+    }
+
+    /// A syntax by its name, or failing that by its token ("rust", "py")
+    fn syntax_by_name(&self, name: &str) -> Option<&SyntaxReference> {
+        self.syntax_set
+            .find_syntax_by_name(name)
+            .or_else(|| self.syntax_set.find_syntax_by_token(name))
+    }
+
+    pub fn theme_name(&self) -> &str {
+        &self.theme_name
+    }
+
+    /// Names of all available syntax themes, sorted
+    pub fn theme_names(&self) -> Vec<&str> {
+        // ThemeSet stores themes in a BTreeMap, so keys are already sorted
+        self.theme_set.themes.keys().map(String::as_str).collect()
+    }
+
+    pub fn has_theme(&self, name: &str) -> bool {
+        self.theme_set.themes.contains_key(name)
+    }
+
+    /// Make a theme loaded from a `.tmTheme` file available as `name`
+    pub fn add_theme(&mut self, name: String, theme: Theme) {
+        let mut themes = self.theme_set.themes.clone();
+        themes.insert(name, theme);
+        self.theme_set = Arc::new(ThemeSet { themes });
+    }
+
+    /// Switch the highlighting theme, returning false if `name` is unknown
+    pub fn set_theme(&mut self, name: &str) -> bool {
+        if !self.theme_set.themes.contains_key(name) {
+            return false;
+        }
+        self.theme_name = name.to_string();
+        true
+    }
+
+    /// Generate the preview of `file_path`. Runs on the preview worker;
+    /// `job` receives a plain-text placeholder before text is rendered.
+    pub fn preview(&self, vfs: &Arc<dyn Vfs>, file_path: &Path, job: &Job) -> (PreviewContent, usize) {
+        let metadata = match vfs.metadata(file_path) {
             Ok(m) => m,
-            Err(e) => return (PreviewContent::Error(format!("Error: {}", e)), 1),
+            Err(e) => {
+                let message = match vfs.read_link(file_path) {
+                    Ok(target) => format!("Broken symlink to {}", target.display()),
+                    Err(_) => format!("Error: {}", e),
+                };
+                return (PreviewContent::Error(message), 1);
+            }
         };
 
-        if metadata.is_dir() {
-            return self.preview_directory(file_path);
+        if metadata.is_dir {
+            return self.preview_directory(vfs.as_ref(), file_path);
         }
 
-        if is_likely_binary(file_path) {
-            let ext = file_path
-                .extension()
-                .map(|e| e.to_string_lossy().to_uppercase())
-                .unwrap_or_else(|| "unknown".to_string());
-            let size = format_size(metadata.len());
-            return (
-                PreviewContent::Binary(format!("Binary file ({})\nSize: {}", ext, size)),
-                2,
-            );
+        if metadata.len == 0 {
+            return (PreviewContent::Empty, 1);
         }
 
-        if metadata.len() == 0 {
-            return (PreviewContent::Empty, 1);
+        // Unreadable archives fall back to the usual binary preview
+        if metadata.is_archive
+            && let Some(kind) = ArchiveKind::detect(file_path)
+            && let Ok(entries) = vfs.list_archive(file_path)
+        {
+            return preview_archive(kind, &entries);
         }
 
-        if metadata.len() > MAX_PREVIEW_BYTES {
-            let size = format_size(metadata.len());
+        let start = match vfs.read(file_path, binary::SNIFF_BYTES as u64) {
+            Ok(bytes) => bytes,
+            Err(e) => return (PreviewContent::Error(format!("Error: {}", e)), 1),
+        };
+        if let Some(kind) = binary::sniff_bytes(&start) {
+            // Databases that fail to open fall back to the hex dump. SQLite
+            // needs a real file, so ones in archives aren't opened.
+            if kind == "SQLite database"
+                && vfs.is_local(file_path)
+                && let Ok(database) = self.preview_database(file_path, metadata.len)
+            {
+                let total_lines = database.lines.len();
+                return (PreviewContent::Database(Arc::new(database)), total_lines);
+            }
+            // So do executables that fail to parse
+            if kind == "ELF"
+                && metadata.len <= MAX_ELF_BYTES
+                && let Ok(bytes) = vfs.read(file_path, metadata.len)
+                && let Ok(info) = elf::parse(&bytes)
+            {
+                let total_lines = info.lines.len();
+                return (PreviewContent::Elf(Arc::new(info)), total_lines);
+            }
+            // Images that fail to decode fall back to the hex dump
+            if IMAGE_KINDS.contains(&kind)
+                && metadata.len <= MAX_IMAGE_BYTES
+                && let Ok(bytes) = vfs.read(file_path, metadata.len)
+                && let Ok(((width, height), image)) = graphics::decode(&bytes)
+            {
+                let header = format!(
+                    "{}, {}×{}, {}",
+                    kind,
+                    width,
+                    height,
+                    format_size(metadata.len)
+                );
+                return (
+                    PreviewContent::Image {
+                        path: file_path.to_path_buf(),
+                        header,
+                        image,
+                    },
+                    1,
+                );
+            }
+            let mut header = format!("Binary file ({}), {}", kind, format_size(metadata.len));
+            // Reading from an offset in an archive member means decoding
+            // everything before it, so members are read once, up to the
+            // preview size limit
+            let bytes = if vfs.is_local(file_path) {
+                None
+            } else {
+                match vfs.read(file_path, self.max_bytes) {
+                    Ok(bytes) => Some(Arc::<[u8]>::from(bytes)),
+                    Err(e) => return (PreviewContent::Error(format!("Error: {}", e)), 1),
+                }
+            };
+            let size = bytes.as_ref().map_or(metadata.len, |b| b.len() as u64);
+            if size < metadata.len {
+                header.push_str(&format!(", first {} shown", format_size(size)));
+            }
             return (
-                PreviewContent::Text(format!("File too large to preview ({})", size)),
-                1,
+                PreviewContent::Hex {
+                    path: file_path.to_path_buf(),
+                    size,
+                    header,
+                    bytes,
+                },
+                binary::hex_rows(size),
             );
         }
 
-        let content = match fs::read_to_string(file_path) {
-            Ok(c) => c,
+        // Large local text files are read a window at a time. Elsewhere
+        // (e.g. in archives) reading from an offset means decoding
+        // everything before it, so they're not previewed.
+        if metadata.len > self.max_bytes {
+            if vfs.is_local(file_path) {
+                let index = LineIndex::build(vfs.clone(), file_path.to_path_buf(), metadata.len);
+                return (PreviewContent::Stream(index), 0);
+            }
+            let size = format_size(metadata.len);
+            let message = format!("File too large to preview ({})", size);
+            return (PreviewContent::Text(vec![Line::from(message)]), 1);
+        }
+
+        // Text with the odd invalid byte (e.g. Latin-1) still previews
+        let content = match vfs.read(file_path, metadata.len) {
+            Ok(bytes) => String::from_utf8(bytes)
+                .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned()),
             Err(e) => return (PreviewContent::Error(format!("Error: {}", e)), 1),
         };
+        self.preview_text(file_path, content, job)
+    }
 
-        let total_lines = content.lines().count();
-
+    /// Markdown rendering, a tree or table for data files or syntax
+    /// highlighting for text content
+    fn preview_text(&self, file_path: &Path, content: String, job: &Job) -> (PreviewContent, usize) {
         // Check if it's markdown
         let ext = file_path
             .extension()
             .map(|e| e.to_string_lossy().to_lowercase())
             .unwrap_or_default();
 
+        // Something to show while the file is rendered, parsed or highlighted
+        let plain = plain_lines(&content);
+        job.placeholder(PreviewContent::Text(plain.clone()), plain.len());
+
         if ext == "md" || ext == "mdx" {
-            let rendered = self.render_markdown(&content);
-            return (PreviewContent::Text(rendered), total_lines);
+            let document = self.render_markdown(&content, job);
+            let total_lines = document.lines.len();
+            return (PreviewContent::Markdown(Arc::new(document)), total_lines);
         }
 
-        // Try syntax highlighting
-        let syntax = self
-            .syntax_set
-            .find_syntax_for_file(file_path)
-            .ok()
-            .flatten()
-            .or_else(|| self.syntax_set.find_syntax_by_extension(&ext))
-            .or_else(|| {
-                let fallback = match ext.as_str() {
-                    "ts" | "tsx" | "mts" | "cts" | "jsx" | "mjs" | "cjs" => Some("js"),
-                    "zsh" | "fish" => Some("sh"),
-                    "yml" => Some("yaml"),
-                    "h" | "hpp" => Some("cpp"),
-                    "kt" => Some("java"),
-                    "scss" | "less" => Some("css"),
-                    "toml" => Some("ini"),   // close enough
-                    "dockerfile" => Some("sh"),
-                    _ => None,
-                };
-                fallback.and_then(|f| self.syntax_set.find_syntax_by_extension(f))
-            });
-
-        if let Some(syntax) = syntax {
-            let theme = &self.theme_set.themes["base16-ocean.dark"];
-            let mut highlighter = HighlightLines::new(syntax, theme);
-            let mut highlighted = String::new();
-
-            for line in LinesWithEndings::from(&content) {
-                if let Ok(ranges) = highlighter.highlight_line(line, &self.syntax_set) {
-                    let escaped = as_24_bit_terminal_escaped(&ranges, false);
-                    highlighted.push_str(&escaped);
+        // Files that don't split into columns are shown as text
+        if table::is_table(file_path)
+            && let Some(table) = table::parse(file_path, &content)
+        {
+            let total_lines = table.rows.len();
+            return (PreviewContent::Table(Arc::new(table)), total_lines);
+        }
+
+        // Data files that don't parse (like JSON with comments) are shown
+        // as text, below the error
+        if let Some(format) = structured::Format::detect(file_path) {
+            match structured::parse(format, &content) {
+                Ok(tree) => {
+                    let total_lines = tree.nodes.len();
+                    return (PreviewContent::Structured(Arc::new(tree)), total_lines);
+                }
+                Err(e) => {
+                    let mut lines = vec![structured::error_line(format, &e), Line::default()];
+                    match self.find_syntax(file_path) {
+                        Some(syntax) => lines.extend(self.highlight(syntax, &content)),
+                        None => lines.extend(plain),
+                    }
+                    let total_lines = lines.len();
+                    return (PreviewContent::Text(lines), total_lines);
                 }
             }
-            highlighted.push_str("\x1b[0m"); // reset
-            return (PreviewContent::Text(highlighted), total_lines);
         }
 
-        (PreviewContent::Text(content), total_lines)
+        // Text with escape sequences (like colored logs) keeps its own colors
+        if !content.contains('\x1b')
+            && let Some(syntax) = self.find_syntax(file_path)
+        {
+            let theme = &self.theme_set.themes[&self.theme_name];
+            let code = Highlighted::new(content, syntax, self.syntax_set.clone(), theme);
+            let total_lines = code.line_count();
+            return (PreviewContent::Code(Arc::new(code)), total_lines);
+        }
+
+        let total_lines = plain.len();
+        (PreviewContent::Text(plain), total_lines)
     }
 
-    /// Render markdown with syntax-highlighted code blocks
-    fn render_markdown(&self, input: &str) -> String {
-        let mut output = String::new();
-        let mut lines = input.lines().peekable();
-        let theme = &self.theme_set.themes["base16-ocean.dark"];
-
-        while let Some(line) = lines.next() {
-            // Fenced code block
-            if line.starts_with("```") {
-                let lang = line.trim_start_matches('`').trim();
-                let mut code_lines: Vec<String> = Vec::new();
-
-                // Collect lines until closing fence
-                while let Some(code_line) = lines.next() {
-                    if code_line.starts_with("```") {
-                        break;
-                    }
-                    code_lines.push(code_line.to_string());
-                }
+    /// Highlight a window of lines from a streamed file. Highlighting starts
+    /// fresh at the first line, so constructs opened above it (like block
+    /// comments) aren't recognized.
+    pub fn highlight_window(&self, file_path: &Path, lines: &[String]) -> Vec<Line<'static>> {
+        let escaped = lines.iter().any(|l| l.contains('\x1b'));
+        match self.find_syntax(file_path).filter(|_| !escaped) {
+            Some(syntax) => self.highlight(syntax, &lines.join("\n")),
+            None => lines.iter().map(|l| parse_ansi_line(l)).collect(),
+        }
+    }
 
-                let code = code_lines.join("\n");
-
-                // Try syntax highlighting
-                let syntax = if !lang.is_empty() {
-                    self.syntax_set
-                        .find_syntax_by_token(lang)
-                        .or_else(|| self.syntax_set.find_syntax_by_extension(lang))
-                        .or_else(|| {
-                            let fallback = match lang {
-                                "ts" | "typescript" | "tsx" | "jsx" | "mjs" | "cjs" => Some("js"),
-                                "zsh" | "fish" => Some("sh"),
-                                "yml" => Some("yaml"),
-                                "dockerfile" => Some("sh"),
-                                _ => None,
-                            };
-                            fallback.and_then(|f| self.syntax_set.find_syntax_by_extension(f))
-                        })
-                } else {
-                    None
-                };
+    /// The syntax for a file: a configured mapping for its name or
+    /// extension, else the syntax claiming its name, extension or first line
+    fn find_syntax(&self, file_path: &Path) -> Option<&SyntaxReference> {
+        let file_name = file_path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        let ext = file_path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
 
-                if let Some(syntax) = syntax {
-                    let mut highlighter = HighlightLines::new(syntax, theme);
-                    let code_with_newlines = code + "\n";
-                    for code_line in LinesWithEndings::from(&code_with_newlines) {
-                        if let Ok(ranges) =
-                            highlighter.highlight_line(code_line, &self.syntax_set)
-                        {
-                            let escaped = as_24_bit_terminal_escaped(&ranges, false);
-                            output.push_str(&format!("  {}", escaped));
-                        }
-                    }
-                    output.push_str(RESET);
-                } else {
-                    // No syntax found — render as dimmed code
-                    for code_line in &code_lines {
-                        output.push_str(&format!("  {DIM}{}{RESET}\n", code_line));
-                    }
-                }
-                output.push('\n');
-                continue;
-            }
+        self.mappings
+            .filenames
+            .get(file_name.as_ref())
+            .or_else(|| self.mappings.extensions.get(&ext))
+            .and_then(|name| self.syntax_by_name(name))
+            .or_else(|| self.syntax_set.find_syntax_for_file(file_path).ok().flatten())
+            .or_else(|| self.syntax_set.find_syntax_by_extension(&ext))
+    }
 
-            // Headings
-            if line.starts_with("# ") {
-                output.push_str(&format!(
-                    "{BOLD}{CYAN}{}{RESET}\n",
-                    &line[2..]
-                ));
-                continue;
-            }
-            if line.starts_with("## ") {
-                output.push_str(&format!(
-                    "{BOLD}{GREEN}{}{RESET}\n",
-                    &line[3..]
-                ));
-                continue;
-            }
-            if line.starts_with("### ") {
-                output.push_str(&format!(
-                    "{BOLD}{YELLOW}{}{RESET}\n",
-                    &line[4..]
-                ));
-                continue;
-            }
-            if line.starts_with("#### ") || line.starts_with("##### ") || line.starts_with("###### ") {
-                let text = line.trim_start_matches('#').trim_start();
-                output.push_str(&format!("{BOLD}{MAGENTA}{text}{RESET}\n"));
-                continue;
-            }
+    /// The syntax for a markdown code block's language
+    fn find_syntax_for_lang(&self, lang: &str) -> Option<&SyntaxReference> {
+        self.mappings
+            .extensions
+            .get(&lang.to_lowercase())
+            .and_then(|name| self.syntax_by_name(name))
+            .or_else(|| self.syntax_set.find_syntax_by_token(lang))
+    }
 
-            // Horizontal rule
-            if (line.starts_with("---") || line.starts_with("***") || line.starts_with("___"))
-                && line.chars().all(|c| c == '-' || c == '*' || c == '_' || c == ' ')
-                && line.len() >= 3
-            {
-                output.push_str(&format!("{DIM}────────────────────────────────{RESET}\n"));
-                continue;
-            }
+    fn highlight(&self, syntax: &SyntaxReference, content: &str) -> Vec<Line<'static>> {
+        let theme = &self.theme_set.themes[&self.theme_name];
+        let mut highlighter = HighlightLines::new(syntax, theme);
+        LinesWithEndings::from(content)
+            .map(|line| match highlighter.highlight_line(line, &self.syntax_set) {
+                Ok(ranges) => highlight::styled_line(&ranges),
+                Err(_) => Line::from(line.trim_end().to_string()),
+            })
+            .collect()
+    }
 
-            // Bullet lists (with optional leading whitespace for nesting)
-            if let Some((indent_level, text)) = strip_bullet_prefix(line) {
-                let indent = "  ".repeat(indent_level + 1);
-                let rendered = render_inline(text);
-                output.push_str(&format!("{indent}{BLUE}•{RESET} {rendered}\n"));
-                continue;
-            }
+    /// Render markdown to the preview width, with code blocks highlighted
+    fn render_markdown(&self, input: &str, job: &Job) -> Document {
+        let highlight = |lang: &str, code: &str| {
+            self.find_syntax_for_lang(lang)
+                .map(|syntax| self.highlight(syntax, code))
+        };
+        markdown::render(input, self.width, job, &highlight)
+    }
 
-            // Numbered lists (with optional leading whitespace)
-            if let Some((indent_level, text)) = strip_numbered_prefix(line) {
-                let indent = "  ".repeat(indent_level + 1);
-                let rendered = render_inline(text);
-                output.push_str(&format!("{indent}{rendered}\n"));
-                continue;
+    /// A summary, then each table, view, index and trigger with its row
+    /// count and highlighted schema
+    fn preview_database(&self, path: &Path, size: u64) -> Result<Database, String> {
+        let mut objects = sqlite::schema(path)?;
+        let count = |kind: &str| objects.iter().filter(|o| o.kind == kind).count();
+        let summary = format!(
+            "SQLite database: {} tables, {} views, {} indices, {}",
+            count("table"),
+            count("view"),
+            count("index"),
+            format_size(size)
+        );
+        let mut lines = vec![Line::from(Span::styled(summary, BOLD)), Line::default()];
+
+        let sql = self.syntax_by_name("SQL");
+        for object in &mut objects {
+            object.line = lines.len();
+            let mut heading = vec![
+                Span::styled(format!("{} ", object.kind), DIM),
+                Span::styled(object.name.clone(), BOLD),
+            ];
+            if let Some(rows) = object.rows {
+                let noun = if rows == 1 { "row" } else { "rows" };
+                heading.push(Span::styled(format!("  {} {}", rows, noun), DIM));
             }
-
-            // Blockquotes
-            if line.starts_with("> ") {
-                let text = &line[2..];
-                let rendered = render_inline(text);
-                output.push_str(&format!("  {DIM}│{RESET} {rendered}\n"));
-                continue;
+            lines.push(Line::from(heading));
+            if let Some(schema) = &object.sql {
+                let schema_lines = match sql {
+                    Some(syntax) => self.highlight(syntax, schema),
+                    None => plain_lines(schema),
+                };
+                lines.extend(schema_lines.into_iter().map(|mut line| {
+                    line.spans.insert(0, Span::raw("  "));
+                    line
+                }));
             }
-
-            // Regular text with inline formatting
-            let rendered = render_inline(line);
-            output.push_str(&rendered);
-            output.push('\n');
+            lines.push(Line::default());
         }
 
-        output
+        Ok(Database {
+            path: path.to_path_buf(),
+            objects,
+            lines,
+        })
     }
 
-    fn preview_directory(&self, dir_path: &Path) -> (PreviewContent, usize) {
-        match fs::read_dir(dir_path) {
+    fn preview_directory(&self, vfs: &dyn Vfs, dir_path: &Path) -> (PreviewContent, usize) {
+        match vfs.read_dir(dir_path) {
             Ok(entries) => {
-                let entries: Vec<_> = entries.flatten().collect();
                 let total = entries.len();
-                let dirs = entries
-                    .iter()
-                    .filter(|e| e.file_type().map(|ft| ft.is_dir()).unwrap_or(false))
-                    .count();
+                let dirs = entries.iter().filter(|e| e.is_dir).count();
                 let files = total - dirs;
                 (
                     PreviewContent::Directory(format!(
@@ -305,140 +503,165 @@ impl Previewer {
     }
 }
 
-/// Render inline markdown formatting: **bold**, *italic*, `code`, [links](url)
-fn render_inline(text: &str) -> String {
-    let mut result = String::new();
-    let chars: Vec<char> = text.chars().collect();
-    let len = chars.len();
-    let mut i = 0;
-
-    while i < len {
-        // Inline code: `text`
-        if chars[i] == '`' {
-            if let Some(end) = find_closing(&chars, i + 1, '`') {
-                let code_text: String = chars[i + 1..end].iter().collect();
-                result.push_str(&format!("{DIM}{CYAN}{code_text}{RESET}"));
-                i = end + 1;
-                continue;
-            }
-        }
-
-        // Bold: **text**
-        if i + 1 < len && chars[i] == '*' && chars[i + 1] == '*' {
-            if let Some(end) = find_double_closing(&chars, i + 2, '*') {
-                let inner: String = chars[i + 2..end].iter().collect();
-                result.push_str(&format!("{BOLD}{inner}{RESET}"));
-                i = end + 2;
-                continue;
-            }
-        }
-
-        // Italic: *text*
-        if chars[i] == '*' && (i + 1 < len && chars[i + 1] != '*') {
-            if let Some(end) = find_closing(&chars, i + 1, '*') {
-                let inner: String = chars[i + 1..end].iter().collect();
-                result.push_str(&format!("{ITALIC}{inner}{RESET}"));
-                i = end + 1;
-                continue;
-            }
-        }
-
-        // Link: [text](url) — show text in blue
-        if chars[i] == '[' {
-            if let Some(bracket_end) = find_closing(&chars, i + 1, ']') {
-                if bracket_end + 1 < len && chars[bracket_end + 1] == '(' {
-                    if let Some(paren_end) = find_closing(&chars, bracket_end + 2, ')') {
-                        let link_text: String = chars[i + 1..bracket_end].iter().collect();
-                        result.push_str(&format!("{BLUE}{link_text}{RESET}"));
-                        i = paren_end + 1;
-                        continue;
-                    }
-                }
-            }
-        }
+/// Lines of a text file without highlighting. Escape sequences in the file
+/// (e.g. a colored log) are applied rather than shown raw.
+fn plain_lines(content: &str) -> Vec<Line<'static>> {
+    content.lines().map(parse_ansi_line).collect()
+}
 
-        result.push(chars[i]);
-        i += 1;
+/// List every entry of an archive with its size and modification time
+fn preview_archive(kind: ArchiveKind, entries: &[Entry]) -> (PreviewContent, usize) {
+    let dirs = entries.iter().filter(|e| e.is_directory).count();
+    let total_size: u64 = entries.iter().map(|e| e.size).sum();
+    let summary = format!(
+        "{}: {} files, {} dirs, {} uncompressed",
+        kind.name(),
+        entries.len() - dirs,
+        dirs,
+        format_size(total_size)
+    );
+    let mut lines = vec![Line::from(Span::styled(summary, BOLD)), Line::default()];
+    for entry in entries {
+        let size = if entry.is_directory {
+            "-".to_string()
+        } else {
+            format_size(entry.size)
+        };
+        let modified = entry.modified.map(archive::format_time).unwrap_or_default();
+        let name = if entry.is_directory {
+            Span::styled(format!("{}/", entry.path), BLUE)
+        } else {
+            Span::raw(entry.path.clone())
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:>10}  {:<16}", size, modified), DIM),
+            Span::raw("  "),
+            name,
+        ]));
     }
-
-    result
+    let total_lines = lines.len();
+    (PreviewContent::Text(lines), total_lines)
 }
 
-fn find_closing(chars: &[char], start: usize, target: char) -> Option<usize> {
-    for i in start..chars.len() {
-        if chars[i] == target {
-            return Some(i);
-        }
+pub fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else if bytes < 1024 * 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1} GB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
     }
-    None
 }
 
-fn find_double_closing(chars: &[char], start: usize, target: char) -> Option<usize> {
-    for i in start..chars.len().saturating_sub(1) {
-        if chars[i] == target && chars[i + 1] == target {
-            return Some(i);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemoryVfs;
+
+    fn preview(files: &[(&str, &[u8])], path: &str) -> (PreviewContent, usize) {
+        let mut vfs = MemoryVfs::new();
+        for (name, contents) in files {
+            vfs.add_file(*name, *contents);
         }
+        vfs.add_symlink("/p/dangling", "gone.txt");
+        let vfs: Arc<dyn Vfs> = Arc::new(vfs);
+        let previewer = Previewer::new(SyntaxSet::load_defaults_newlines());
+        previewer.preview(&vfs, Path::new(path), &Job::standalone())
     }
-    None
-}
-
-/// Strip bullet prefix (- or *) with optional leading whitespace, returns (indent_level, text)
-fn strip_bullet_prefix(line: &str) -> Option<(usize, &str)> {
-    let trimmed = line.trim_start();
-    let leading_spaces = line.len() - trimmed.len();
-    let indent_level = leading_spaces / 2;
 
-    if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
-        Some((indent_level, &trimmed[2..]))
-    } else {
-        None
+    #[test]
+    fn directories_empty_files_and_broken_links() {
+        let files: &[(&str, &[u8])] = &[("/p/src/lib.rs", b"pub fn f() {}"), ("/p/empty", b"")];
+        let (content, _) = preview(files, "/p");
+        let PreviewContent::Directory(summary) = content else {
+            panic!("expected a directory summary");
+        };
+        assert_eq!(summary, "Directory: 3 items (1 dirs, 2 files)");
+        assert!(matches!(preview(files, "/p/empty").0, PreviewContent::Empty));
+        let (content, _) = preview(files, "/p/dangling");
+        let PreviewContent::Error(message) = content else {
+            panic!("expected an error");
+        };
+        assert_eq!(message, "Broken symlink to gone.txt");
     }
-}
 
-/// Strip numbered prefix with optional leading whitespace, returns (indent_level, text)
-fn strip_numbered_prefix(line: &str) -> Option<(usize, &str)> {
-    let trimmed = line.trim_start();
-    let leading_spaces = line.len() - trimmed.len();
-    let indent_level = leading_spaces / 2;
+    #[test]
+    fn binary_files_are_hex_dumped_from_memory() {
+        let files: &[(&str, &[u8])] = &[("/p/blob.bin", b"\x00\x01\x02binary\x00data")];
+        let (content, rows) = preview(files, "/p/blob.bin");
+        let PreviewContent::Hex { size, header, bytes, .. } = content else {
+            panic!("expected a hex dump");
+        };
+        assert_eq!((size, rows), (14, 1));
+        assert!(header.starts_with("Binary file (data)"), "{}", header);
+        // Not on the local disk, so the bytes are read up front
+        assert_eq!(bytes.as_deref().map(<[u8]>::len), Some(14));
+    }
 
-    let bytes = trimmed.as_bytes();
-    let mut i = 0;
-    while i < bytes.len() && bytes[i].is_ascii_digit() {
-        i += 1;
+    #[test]
+    fn text_files_are_highlighted_or_keep_their_escapes() {
+        let files: &[(&str, &[u8])] = &[
+            ("/p/main.rs", b"fn main() {\n    println!(\"hi\");\n}\n"),
+            ("/p/build.log", b"\x1b[31merror\x1b[0m: failed\nok\n"),
+        ];
+        assert!(matches!(preview(files, "/p/main.rs"), (PreviewContent::Code(_), 3)));
+        // Escape sequences keep their own colors instead of being highlighted
+        let (content, rows) = preview(files, "/p/build.log");
+        let PreviewContent::Text(lines) = content else {
+            panic!("expected plain text");
+        };
+        assert_eq!(rows, 2);
+        assert_eq!(lines[0].spans[0].content, "error");
+        assert_eq!(lines[0].spans[0].style.fg, Some(Color::Red));
     }
-    if i > 0 && i < bytes.len() && (bytes[i] == b'.' || bytes[i] == b')') {
-        let rest = &trimmed[i + 1..];
-        if rest.starts_with(' ') {
-            return Some((indent_level, rest.trim_start()));
-        }
+
+    #[test]
+    fn delimited_files_become_tables() {
+        let files: &[(&str, &[u8])] = &[
+            ("/p/people.csv", b"name,age\nada,36\nalan,41\n"),
+            ("/p/notes.csv", b"one column\nper line\n"),
+        ];
+        let (content, rows) = preview(files, "/p/people.csv");
+        let PreviewContent::Table(table) = content else {
+            panic!("expected a table");
+        };
+        assert_eq!(rows, 2);
+        assert_eq!(table.header.as_deref(), Some(&["name".to_string(), "age".to_string()][..]));
+        // Files that don't look delimited are shown as text
+        assert!(!matches!(preview(files, "/p/notes.csv").0, PreviewContent::Table(_)));
     }
-    None
-}
 
-fn is_likely_binary(file_path: &Path) -> bool {
-    const BINARY_EXTS: &[&str] = &[
-        "png", "jpg", "jpeg", "gif", "bmp", "ico", "webp", "avif", "mp3", "mp4", "wav", "avi",
-        "mkv", "mov", "flac", "ogg", "zip", "gz", "tar", "bz2", "xz", "7z", "rar", "pdf", "doc",
-        "docx", "xls", "xlsx", "ppt", "pptx", "exe", "dll", "so", "dylib", "bin", "o", "a",
-        "woff", "woff2", "ttf", "otf", "eot", "sqlite", "db",
-    ];
-    file_path
-        .extension()
-        .map(|e| {
-            let ext = e.to_string_lossy().to_lowercase();
-            BINARY_EXTS.contains(&ext.as_str())
-        })
-        .unwrap_or(false)
-}
+    #[test]
+    fn markdown_files_are_rendered() {
+        let files: &[(&str, &[u8])] = &[("/p/README.md", b"# Title\n\nSome [link](main.rs).\n")];
+        let (content, rows) = preview(files, "/p/README.md");
+        let PreviewContent::Markdown(document) = content else {
+            panic!("expected markdown");
+        };
+        assert_eq!(rows, 3);
+        assert_eq!(document.links.len(), 1);
+    }
 
-fn format_size(bytes: u64) -> String {
-    if bytes < 1024 {
-        format!("{} B", bytes)
-    } else if bytes < 1024 * 1024 {
-        format!("{:.1} KB", bytes as f64 / 1024.0)
-    } else if bytes < 1024 * 1024 * 1024 {
-        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
-    } else {
-        format!("{:.1} GB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+    #[test]
+    fn data_files_that_dont_parse_are_shown_as_text() {
+        let files: &[(&str, &[u8])] = &[
+            ("/p/a.json", b"{\"name\": \"x\", \"tags\": [1, 2]}"),
+            ("/p/tsconfig.json", b"{\n  // comment\n  \"strict\": true,\n}\n"),
+        ];
+        assert!(matches!(preview(files, "/p/a.json"), (PreviewContent::Structured(_), 5)));
+        let (content, rows) = preview(files, "/p/tsconfig.json");
+        let PreviewContent::Text(lines) = content else {
+            panic!("expected text");
+        };
+        assert_eq!(rows, 6);
+        let text: Vec<String> = lines
+            .iter()
+            .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect::<String>())
+            .collect();
+        assert!(text[0].starts_with("Invalid JSON at line 2, column 3:"), "{}", text[0]);
+        assert_eq!(text[1], "");
+        assert_eq!(text[3].trim_end(), "  // comment");
     }
 }
@@ -0,0 +1,116 @@
+use std::path::{Path, PathBuf};
+
+use ratatui::text::Line;
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+
+/// A database previewed as a listing of its schema
+pub struct Database {
+    pub path: PathBuf,
+    pub objects: Vec<Object>,
+    /// Each object's heading followed by its schema
+    pub lines: Vec<Line<'static>>,
+}
+
+/// A table, view, index or trigger
+pub struct Object {
+    pub kind: String,
+    pub name: String,
+    pub sql: Option<String>,
+    /// Rows in a table, if counting them worked. Views aren't counted.
+    pub rows: Option<u64>,
+    /// Columns of a table or view
+    pub columns: usize,
+    /// Line of its heading in the listing
+    pub line: usize,
+}
+
+impl Object {
+    /// Tables and views have rows to browse
+    pub fn has_rows(&self) -> bool {
+        self.kind == "table" || self.kind == "view"
+    }
+}
+
+/// One page of a table's rows, as text
+pub struct Page {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Open read-only, so previewing never changes the file
+pub fn open(path: &Path) -> Result<Connection, String> {
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    Connection::open_with_flags(path, flags).map_err(|e| e.to_string())
+}
+
+/// Every object in the schema, tables first, with row counts for tables.
+/// `line` is left for the caller to fill in.
+pub fn schema(path: &Path) -> Result<Vec<Object>, String> {
+    let connection = open(path)?;
+    let mut statement = connection
+        .prepare(
+            "SELECT type, name, sql FROM sqlite_schema \
+             ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'view' THEN 1 WHEN 'index' THEN 2 ELSE 3 END, name",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut objects: Vec<Object> = statement
+        .query_map([], |row| {
+            Ok(Object {
+                kind: row.get(0)?,
+                name: row.get(1)?,
+                sql: row.get(2)?,
+                rows: None,
+                columns: 0,
+                line: 0,
+            })
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())?;
+
+    for object in objects.iter_mut().filter(|o| o.has_rows()) {
+        let name = quote(&object.name);
+        // Counting a view runs its whole query, so views are paged without
+        // a count
+        if object.kind == "table" {
+            object.rows = connection
+                .query_row(&format!("SELECT count(*) FROM {}", name), [], |row| row.get::<_, i64>(0))
+                .ok()
+                .map(|n| n as u64);
+        }
+        object.columns = connection
+            .prepare(&format!("SELECT * FROM {}", name))
+            .map_or(0, |statement| statement.column_count());
+    }
+    Ok(objects)
+}
+
+/// Up to `limit` rows of a table or view, starting at row `offset`
+pub fn page(connection: &Connection, table: &str, offset: usize, limit: usize) -> Result<Page, String> {
+    let query = format!("SELECT * FROM {} LIMIT ? OFFSET ?", quote(table));
+    let mut statement = connection.prepare(&query).map_err(|e| e.to_string())?;
+    let columns: Vec<String> = statement.column_names().into_iter().map(str::to_string).collect();
+    let count = columns.len();
+    let rows = statement
+        .query_map([limit as i64, offset as i64], |row| {
+            (0..count).map(|i| row.get_ref(i).map(format_value)).collect()
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())?;
+    Ok(Page { columns, rows })
+}
+
+fn format_value(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(n) => n.to_string(),
+        ValueRef::Real(n) => n.to_string(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+        ValueRef::Blob(bytes) => format!("<blob, {} bytes>", bytes.len()),
+    }
+}
+
+/// An identifier quoted for SQL
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
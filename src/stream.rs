@@ -0,0 +1,206 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::vfs::Vfs;
+
+/// The offset of every this-many-th line start is kept, so the index stays
+/// small for files with millions of lines
+const CHECKPOINT_LINES: usize = 128;
+
+/// Bytes read per step while indexing
+const INDEX_CHUNK: u64 = 1024 * 1024;
+
+/// Bytes read per step when fetching lines for display
+const READ_BLOCK: u64 = 64 * 1024;
+
+/// Stop reading a window after this much data (a file may be one huge line)
+const MAX_WINDOW_BYTES: usize = 4 * 1024 * 1024;
+
+/// Longer lines are cut off for display
+const MAX_LINE_BYTES: usize = 16 * 1024;
+
+/// Where lines start in a file too large to read in full. Built on a
+/// background thread; lines can be read as soon as they're indexed.
+pub struct LineIndex {
+    path: PathBuf,
+    state: Mutex<IndexState>,
+}
+
+struct IndexState {
+    /// File size, as far as known (it grows if the file is appended to)
+    size: u64,
+    /// Offset of line `i * CHECKPOINT_LINES`
+    checkpoints: Vec<u64>,
+    newlines: usize,
+    indexed: u64,
+    /// The indexed data ends without a newline, so there's one more line
+    partial_last_line: bool,
+    complete: bool,
+}
+
+impl LineIndex {
+    /// Start indexing `path` in the background. The thread stops early if
+    /// the index is dropped.
+    pub fn build(vfs: Arc<dyn Vfs>, path: PathBuf, size: u64) -> Arc<LineIndex> {
+        let index = Arc::new(LineIndex {
+            path,
+            state: Mutex::new(IndexState {
+                size,
+                checkpoints: vec![0],
+                newlines: 0,
+                indexed: 0,
+                partial_last_line: false,
+                complete: false,
+            }),
+        });
+
+        let weak = Arc::downgrade(&index);
+        thread::spawn(move || {
+            let mut offset = 0;
+            loop {
+                let Some(index) = weak.upgrade() else {
+                    return;
+                };
+                let chunk = match vfs.read_range(&index.path, offset, INDEX_CHUNK) {
+                    Ok(chunk) if !chunk.is_empty() => chunk,
+                    _ => {
+                        index.lock().complete = true;
+                        return;
+                    }
+                };
+                index.lock().add(&chunk);
+                offset += chunk.len() as u64;
+            }
+        });
+
+        index
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, IndexState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn size(&self) -> u64 {
+        self.lock().size
+    }
+
+    /// Number of lines indexed so far
+    pub fn line_count(&self) -> usize {
+        let state = self.lock();
+        state.newlines + usize::from(state.complete && state.partial_last_line)
+    }
+
+    /// Rough memory use, for the preview cache. It grows as indexing goes on.
+    pub fn weight(&self) -> usize {
+        std::mem::size_of::<LineIndex>() + self.lock().checkpoints.len() * std::mem::size_of::<u64>()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.lock().complete
+    }
+
+    /// Share of the file indexed so far, 0-100
+    pub fn progress(&self) -> u64 {
+        let state = self.lock();
+        if state.complete || state.size == 0 {
+            100
+        } else {
+            state.indexed * 100 / state.size
+        }
+    }
+
+    /// Index data appended to the file since indexing finished. Returns
+    /// false without doing anything while the first pass is still running
+    /// (it reads up to the end of the file, so it picks up new data anyway).
+    pub fn catch_up(&self, vfs: &dyn Vfs) -> io::Result<bool> {
+        let mut state = self.lock();
+        if !state.complete {
+            return Ok(false);
+        }
+        loop {
+            let chunk = vfs.read_range(&self.path, state.indexed, INDEX_CHUNK)?;
+            if chunk.is_empty() {
+                return Ok(true);
+            }
+            state.add(&chunk);
+        }
+    }
+
+    /// Read up to `count` lines starting at line `first` (0-based)
+    pub fn read_lines(&self, vfs: &dyn Vfs, first: usize, count: usize) -> io::Result<Vec<String>> {
+        let (mut offset, mut skip) = {
+            let state = self.lock();
+            let checkpoint = (first / CHECKPOINT_LINES).min(state.checkpoints.len() - 1);
+            (
+                state.checkpoints[checkpoint],
+                first - checkpoint * CHECKPOINT_LINES,
+            )
+        };
+
+        let mut lines = Vec::with_capacity(count);
+        let mut line = Vec::new();
+        let mut read = 0;
+        while lines.len() < count {
+            let block = vfs.read_range(&self.path, offset, READ_BLOCK)?;
+            if block.is_empty() || read > MAX_WINDOW_BYTES {
+                if skip == 0 && !line.is_empty() {
+                    lines.push(decode_line(&line));
+                }
+                break;
+            }
+            offset += block.len() as u64;
+            read += block.len();
+
+            let mut rest = &block[..];
+            while let Some(pos) = rest.iter().position(|&b| b == b'\n') {
+                if skip > 0 {
+                    skip -= 1;
+                } else {
+                    append_capped(&mut line, &rest[..pos]);
+                    lines.push(decode_line(&line));
+                    if lines.len() == count {
+                        break;
+                    }
+                }
+                line.clear();
+                rest = &rest[pos + 1..];
+            }
+            if skip == 0 {
+                append_capped(&mut line, rest);
+            }
+        }
+        Ok(lines)
+    }
+}
+
+impl IndexState {
+    /// Count the lines in `chunk`, the data following what's indexed
+    fn add(&mut self, chunk: &[u8]) {
+        let offset = self.indexed;
+        for (i, _) in chunk.iter().enumerate().filter(|(_, b)| **b == b'\n') {
+            self.newlines += 1;
+            if self.newlines.is_multiple_of(CHECKPOINT_LINES) {
+                self.checkpoints.push(offset + i as u64 + 1);
+            }
+        }
+        self.indexed += chunk.len() as u64;
+        self.size = self.size.max(self.indexed);
+        self.partial_last_line = chunk.last() != Some(&b'\n');
+    }
+}
+
+fn append_capped(line: &mut Vec<u8>, bytes: &[u8]) {
+    let room = MAX_LINE_BYTES.saturating_sub(line.len());
+    line.extend_from_slice(&bytes[..bytes.len().min(room)]);
+}
+
+fn decode_line(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    String::from_utf8_lossy(bytes).into_owned()
+}
@@ -0,0 +1,491 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+
+const KEY: Style = Style::new().fg(Color::Blue).add_modifier(Modifier::BOLD);
+const STRING: Style = Style::new().fg(Color::Green);
+const NUMBER: Style = Style::new().fg(Color::Cyan);
+const LITERAL: Style = Style::new().fg(Color::Yellow);
+const DIM: Style = Style::new().add_modifier(Modifier::DIM);
+const ERROR: Style = Style::new().fg(Color::Red).add_modifier(Modifier::BOLD);
+
+/// Columns per nesting level
+const INDENT: usize = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// The format of a file, by name
+    pub fn detect(path: &Path) -> Option<Format> {
+        let name = path.file_name()?.to_string_lossy();
+        if name == "Cargo.lock" {
+            return Some(Format::Toml);
+        }
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        match ext.as_str() {
+            "json" | "geojson" | "webmanifest" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Json => "JSON",
+            Format::Yaml => "YAML",
+            Format::Toml => "TOML",
+        }
+    }
+}
+
+/// Where a node sits in its parent
+pub enum Key {
+    Root,
+    Index(usize),
+    Name(String),
+}
+
+pub enum Value {
+    Object(usize),
+    Array(usize),
+    String(String),
+    /// Numbers, booleans, null and dates, as written
+    Literal(String, Style),
+}
+
+pub struct Node {
+    pub key: Key,
+    pub value: Value,
+    pub depth: usize,
+    pub parent: Option<usize>,
+    /// Index just past the node's last descendant
+    pub end: usize,
+}
+
+/// A parsed document as a flat list of nodes in document order, so a
+/// node's descendants are the nodes up to its `end`
+pub struct Tree {
+    pub nodes: Vec<Node>,
+}
+
+/// A parse error, with a 1-based position when the parser reports one
+pub struct ParseError {
+    pub message: String,
+    pub position: Option<(usize, usize)>,
+}
+
+/// Parse `content` as `format`
+pub fn parse(format: Format, content: &str) -> Result<Tree, ParseError> {
+    let mut builder = Builder::default();
+    match format {
+        Format::Json => {
+            let value: serde_json::Value = serde_json::from_str(content).map_err(|e| ParseError {
+                message: strip_position(&e.to_string()),
+                position: Some((e.line(), e.column())),
+            })?;
+            builder.json(Key::Root, &value, None);
+        }
+        Format::Yaml => {
+            let mut documents = Vec::new();
+            for document in serde_yaml_ng::Deserializer::from_str(content) {
+                let mut value: serde_yaml_ng::Value =
+                    serde::Deserialize::deserialize(document).map_err(yaml_error)?;
+                value.apply_merge().map_err(yaml_error)?;
+                documents.push(value);
+            }
+            // A stream of documents is shown as an array of them
+            match documents.len() {
+                0 => builder.leaf(Key::Root, Value::Literal("null".to_string(), LITERAL), None),
+                1 => builder.yaml(Key::Root, &documents[0], None),
+                _ => {
+                    let root = builder.push(Key::Root, Value::Array(documents.len()), None);
+                    for (i, document) in documents.iter().enumerate() {
+                        builder.yaml(Key::Index(i), document, Some(root));
+                    }
+                    builder.close(root);
+                }
+            }
+        }
+        Format::Toml => {
+            let table: toml::Table = content.parse().map_err(|e: toml::de::Error| ParseError {
+                message: e.message().to_string(),
+                position: e.span().map(|span| position(content, span.start)),
+            })?;
+            let root = builder.push(Key::Root, Value::Object(table.len()), None);
+            for (key, value) in &table {
+                builder.toml(Key::Name(key.clone()), value, Some(root));
+            }
+            builder.close(root);
+        }
+    }
+    Ok(Tree {
+        nodes: builder.nodes,
+    })
+}
+
+fn yaml_error(e: serde_yaml_ng::Error) -> ParseError {
+    ParseError {
+        message: strip_position(&e.to_string()),
+        position: e.location().map(|l| (l.line(), l.column())),
+    }
+}
+
+/// serde_json and serde_yaml end their messages with the position, which
+/// is shown separately
+fn strip_position(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(i) => message[..i].to_string(),
+        None => message.to_string(),
+    }
+}
+
+/// 1-based line and column (in characters) of a byte offset
+fn position(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+#[derive(Default)]
+struct Builder {
+    nodes: Vec<Node>,
+}
+
+impl Builder {
+    fn push(&mut self, key: Key, value: Value, parent: Option<usize>) -> usize {
+        let depth = parent.map_or(0, |p| self.nodes[p].depth + 1);
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            key,
+            value,
+            depth,
+            parent,
+            end: index + 1,
+        });
+        index
+    }
+
+    fn leaf(&mut self, key: Key, value: Value, parent: Option<usize>) {
+        self.push(key, value, parent);
+    }
+
+    fn close(&mut self, index: usize) {
+        self.nodes[index].end = self.nodes.len();
+    }
+
+    fn json(&mut self, key: Key, value: &serde_json::Value, parent: Option<usize>) {
+        use serde_json::Value as J;
+        match value {
+            J::Object(map) => {
+                let index = self.push(key, Value::Object(map.len()), parent);
+                for (name, child) in map {
+                    self.json(Key::Name(name.clone()), child, Some(index));
+                }
+                self.close(index);
+            }
+            J::Array(items) => {
+                let index = self.push(key, Value::Array(items.len()), parent);
+                for (i, child) in items.iter().enumerate() {
+                    self.json(Key::Index(i), child, Some(index));
+                }
+                self.close(index);
+            }
+            J::String(s) => self.leaf(key, Value::String(s.clone()), parent),
+            J::Number(n) => self.leaf(key, Value::Literal(n.to_string(), NUMBER), parent),
+            J::Bool(b) => self.leaf(key, Value::Literal(b.to_string(), LITERAL), parent),
+            J::Null => self.leaf(key, Value::Literal("null".to_string(), LITERAL), parent),
+        }
+    }
+
+    fn yaml(&mut self, key: Key, value: &serde_yaml_ng::Value, parent: Option<usize>) {
+        use serde_yaml_ng::Value as Y;
+        match value {
+            Y::Mapping(map) => {
+                let index = self.push(key, Value::Object(map.len()), parent);
+                for (name, child) in map {
+                    self.yaml(Key::Name(yaml_key(name)), child, Some(index));
+                }
+                self.close(index);
+            }
+            Y::Sequence(items) => {
+                let index = self.push(key, Value::Array(items.len()), parent);
+                for (i, child) in items.iter().enumerate() {
+                    self.yaml(Key::Index(i), child, Some(index));
+                }
+                self.close(index);
+            }
+            // The tag is dropped; the value is what's worth browsing
+            Y::Tagged(tagged) => self.yaml(key, &tagged.value, parent),
+            Y::String(s) => self.leaf(key, Value::String(s.clone()), parent),
+            Y::Number(n) => self.leaf(key, Value::Literal(n.to_string(), NUMBER), parent),
+            Y::Bool(b) => self.leaf(key, Value::Literal(b.to_string(), LITERAL), parent),
+            Y::Null => self.leaf(key, Value::Literal("null".to_string(), LITERAL), parent),
+        }
+    }
+
+    fn toml(&mut self, key: Key, value: &toml::Value, parent: Option<usize>) {
+        use toml::Value as T;
+        match value {
+            T::Table(table) => {
+                let index = self.push(key, Value::Object(table.len()), parent);
+                for (name, child) in table {
+                    self.toml(Key::Name(name.clone()), child, Some(index));
+                }
+                self.close(index);
+            }
+            T::Array(items) => {
+                let index = self.push(key, Value::Array(items.len()), parent);
+                for (i, child) in items.iter().enumerate() {
+                    self.toml(Key::Index(i), child, Some(index));
+                }
+                self.close(index);
+            }
+            T::String(s) => self.leaf(key, Value::String(s.clone()), parent),
+            T::Integer(n) => self.leaf(key, Value::Literal(n.to_string(), NUMBER), parent),
+            T::Float(n) => self.leaf(key, Value::Literal(n.to_string(), NUMBER), parent),
+            T::Boolean(b) => self.leaf(key, Value::Literal(b.to_string(), LITERAL), parent),
+            T::Datetime(d) => self.leaf(key, Value::Literal(d.to_string(), LITERAL), parent),
+        }
+    }
+}
+
+/// YAML keys can be any value; scalars are shown as written
+fn yaml_key(key: &serde_yaml_ng::Value) -> String {
+    use serde_yaml_ng::Value as Y;
+    match key {
+        Y::String(s) => s.clone(),
+        Y::Number(n) => n.to_string(),
+        Y::Bool(b) => b.to_string(),
+        Y::Null => "null".to_string(),
+        other => serde_yaml_ng::to_string(other)
+            .map(|s| s.trim_end().replace('\n', " "))
+            .unwrap_or_default(),
+    }
+}
+
+impl Tree {
+    /// The nodes shown when the nodes in `collapsed` hide their children
+    pub fn rows(&self, collapsed: &HashSet<usize>) -> Vec<usize> {
+        let mut rows = Vec::new();
+        let mut i = 0;
+        while i < self.nodes.len() {
+            rows.push(i);
+            i = if collapsed.contains(&i) { self.nodes[i].end } else { i + 1 };
+        }
+        rows
+    }
+
+    pub fn has_children(&self, index: usize) -> bool {
+        self.nodes[index].end > index + 1
+    }
+
+    /// Every node with children except the root
+    pub fn containers(&self) -> impl Iterator<Item = usize> + '_ {
+        (1..self.nodes.len()).filter(|&i| self.has_children(i))
+    }
+
+    /// Rough memory use, for the preview cache
+    pub fn weight(&self) -> usize {
+        self.nodes
+            .iter()
+            .map(|node| {
+                let text = match (&node.key, &node.value) {
+                    (Key::Name(k), Value::String(s) | Value::Literal(s, _)) => k.len() + s.len(),
+                    (Key::Name(k), _) => k.len(),
+                    (_, Value::String(s) | Value::Literal(s, _)) => s.len(),
+                    _ => 0,
+                };
+                text + std::mem::size_of::<Node>()
+            })
+            .sum()
+    }
+
+    /// The node's location as a jq path, like `.dependencies.serde[0]`
+    pub fn path(&self, index: usize) -> String {
+        let mut parts = Vec::new();
+        let mut current = Some(index);
+        while let Some(i) = current {
+            let node = &self.nodes[i];
+            match &node.key {
+                Key::Root => {}
+                Key::Index(n) => parts.push(format!("[{}]", n)),
+                Key::Name(name) if is_identifier(name) => parts.push(format!(".{}", name)),
+                Key::Name(name) => parts.push(format!(".{}", serde_json::Value::from(name.as_str()))),
+            }
+            current = node.parent;
+        }
+        if parts.is_empty() {
+            return ".".to_string();
+        }
+        parts.reverse();
+        let path = parts.concat();
+        // A leading index needs a dot in jq: `.[0]`
+        if path.starts_with('[') { format!(".{}", path) } else { path }
+    }
+
+    /// One row of the tree: the key, then the value or, for objects and
+    /// arrays, a fold marker and the number of entries
+    pub fn line(&self, index: usize, collapsed: bool) -> Line<'static> {
+        let node = &self.nodes[index];
+        let mut spans = vec![Span::raw(" ".repeat(node.depth * INDENT))];
+        let container = matches!(node.value, Value::Object(_) | Value::Array(_));
+        let marker = match () {
+            _ if !container || !self.has_children(index) => "  ",
+            _ if collapsed => "▶ ",
+            _ => "▼ ",
+        };
+        spans.push(Span::styled(marker, DIM));
+        match &node.key {
+            Key::Root => {}
+            Key::Index(n) => spans.push(Span::styled(format!("{}: ", n), DIM)),
+            Key::Name(name) => {
+                spans.push(Span::styled(name.clone(), KEY));
+                spans.push(Span::raw(": "));
+            }
+        }
+        match &node.value {
+            Value::Object(count) => {
+                spans.push(Span::raw("{"));
+                spans.push(Span::styled(plural(*count, "key", "keys"), DIM));
+                spans.push(Span::raw("}"));
+            }
+            Value::Array(count) => {
+                spans.push(Span::raw("["));
+                spans.push(Span::styled(plural(*count, "item", "items"), DIM));
+                spans.push(Span::raw("]"));
+            }
+            Value::String(s) => {
+                // Quoted and escaped as in JSON, keeping each value on one row
+                spans.push(Span::styled(serde_json::Value::from(s.as_str()).to_string(), STRING));
+            }
+            Value::Literal(s, style) => spans.push(Span::styled(s.clone(), *style)),
+        }
+        Line::from(spans)
+    }
+}
+
+fn plural(count: usize, one: &str, many: &str) -> String {
+    format!("{} {}", count, if count == 1 { one } else { many })
+}
+
+/// Keys that jq accepts after a bare dot
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A description of the error, shown above the source
+pub fn error_line(format: Format, error: &ParseError) -> Line<'static> {
+    let description = match error.position {
+        Some((line, column)) => format!(
+            "Invalid {} at line {}, column {}: {}",
+            format.name(),
+            line,
+            column,
+            error.message
+        ),
+        None => format!("Invalid {}: {}", format.name(), error.message),
+    };
+    Line::from(Span::styled(description, ERROR))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(format: Format, content: &str) -> Vec<String> {
+        let tree = parse(format, content).unwrap_or_else(|e| panic!("{}", e.message));
+        (0..tree.nodes.len())
+            .map(|i| tree.line(i, false).spans.iter().map(|s| s.content.as_ref()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn json_objects_and_arrays_become_nodes() {
+        assert_eq!(
+            lines(Format::Json, r#"{"name": "x", "tags": [1, true, null], "nested": {"a": 1.5}}"#),
+            [
+                "▼ {3 keys}",
+                "    name: \"x\"",
+                "  ▼ tags: [3 items]",
+                "      0: 1",
+                "      1: true",
+                "      2: null",
+                "  ▼ nested: {1 key}",
+                "      a: 1.5",
+            ]
+        );
+        let tree = parse(Format::Json, r#"{"a": {"b": [1, 2]}}"#).ok().unwrap();
+        let paths: Vec<String> = (0..tree.nodes.len()).map(|i| tree.path(i)).collect();
+        assert_eq!(paths, [".", ".a", ".a.b", ".a.b[0]", ".a.b[1]"]);
+        assert_eq!(tree.nodes[1].end, 5);
+    }
+
+    #[test]
+    fn yaml_merges_keys_and_lists_documents() {
+        assert_eq!(
+            lines(Format::Yaml, "base: &b\n  x: 1\nderived:\n  <<: *b\n  y: two\n---\n- 3\n"),
+            [
+                "▼ [2 items]",
+                "  ▼ 0: {2 keys}",
+                "    ▼ base: {1 key}",
+                "        x: 1",
+                "    ▼ derived: {2 keys}",
+                "        y: \"two\"",
+                "        x: 1",
+                "  ▼ 1: [1 item]",
+                "      0: 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn toml_tables_and_arrays_of_tables() {
+        assert_eq!(
+            lines(
+                Format::Toml,
+                "title = \"t\"\n[owner]\nborn = 1979-05-27T07:32:00Z\n[[items]]\nn = 1\n[[items]]\nn = 2\n"
+            ),
+            [
+                "▼ {3 keys}",
+                "    title: \"t\"",
+                "  ▼ owner: {1 key}",
+                "      born: 1979-05-27T07:32:00Z",
+                "  ▼ items: [2 items]",
+                "    ▼ 0: {1 key}",
+                "        n: 1",
+                "    ▼ 1: {1 key}",
+                "        n: 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_have_positions() {
+        let cases = [
+            (Format::Json, "{\n  \"a\": 1,\n}", (3, 1)),
+            (Format::Yaml, "a: [1,\nb: 2", (3, 1)),
+            (Format::Toml, "a = 1\nb = \n", (2, 5)),
+        ];
+        for (format, content, position) in cases {
+            let error = parse(format, content).err().unwrap();
+            assert_eq!(error.position, Some(position), "{:?}", format);
+        }
+        let error = parse(Format::Json, "{\n  \"a\": 1,\n}").err().unwrap();
+        assert_eq!(
+            error_line(Format::Json, &error).spans[0].content,
+            "Invalid JSON at line 3, column 1: trailing comma"
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use syntect::dumps;
+use syntect::parsing::{SyntaxDefinition, SyntaxSet};
+
+use crate::config;
+
+/// `$XDG_CONFIG_HOME/browse/syntaxes`, where extra `.sublime-syntax` files go
+pub fn user_syntax_dir() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("syntaxes"))
+}
+
+/// The bundled syntaxes plus the `.sublime-syntax` files in `dir`. Returns
+/// the set and a message for each file that failed to load.
+///
+/// Linking a syntax set takes a while, so a set with user syntaxes is
+/// dumped to the cache directory and reused until one of the files changes.
+pub fn load_syntaxes(dir: &Path) -> (SyntaxSet, Vec<String>) {
+    let files = syntax_files(dir);
+    if files.is_empty() {
+        return (two_face::syntax::extra_newlines(), Vec::new());
+    }
+
+    let key = fingerprint(&files);
+    let cache = cache_paths();
+    if let Some((dump, key_file)) = &cache
+        && fs::read_to_string(key_file).is_ok_and(|cached| cached == key)
+        && let Ok(set) = dumps::from_uncompressed_dump_file(dump)
+    {
+        return (set, Vec::new());
+    }
+
+    let mut builder = two_face::syntax::extra_newlines().into_builder();
+    let mut errors = Vec::new();
+    for path in &files {
+        let name = path.file_stem().map(|s| s.to_string_lossy().into_owned());
+        let definition = fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|text| {
+            SyntaxDefinition::load_from_str(&text, true, name.as_deref()).map_err(|e| e.to_string())
+        });
+        match definition {
+            Ok(definition) => builder.add(definition),
+            Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    let set = builder.build();
+
+    // Broken files would load from the cache silently, so only a clean set
+    // is cached
+    if errors.is_empty()
+        && let Some((dump, key_file)) = &cache
+    {
+        let saved = dump
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| dumps::dump_to_uncompressed_file(&set, dump).map_err(std::io::Error::other))
+            .and_then(|_| fs::write(key_file, &key));
+        if saved.is_err() {
+            let _ = fs::remove_file(key_file);
+        }
+    }
+    (set, errors)
+}
+
+/// `.sublime-syntax` files in `dir`, sorted
+fn syntax_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sublime-syntax"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Identifies a set of syntax files: their paths, sizes and modification
+/// times, plus the program version (which decides the bundled syntaxes)
+fn fingerprint(files: &[PathBuf]) -> String {
+    let mut key = format!("browse {}\n", env!("CARGO_PKG_VERSION"));
+    for path in files {
+        let (size, modified) = fs::metadata(path)
+            .map(|m| {
+                let modified = m
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |d| d.as_nanos());
+                (m.len(), modified)
+            })
+            .unwrap_or_default();
+        key.push_str(&format!("{} {} {}\n", path.display(), size, modified));
+    }
+    key
+}
+
+/// The dumped syntax set and the fingerprint it was built from
+fn cache_paths() -> Option<(PathBuf, PathBuf)> {
+    let dir = dirs::cache_dir()?.join("browse");
+    Some((dir.join("syntaxes.packdump"), dir.join("syntaxes.key")))
+}
@@ -0,0 +1,320 @@
+use std::path::Path;
+
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use unicode_width::UnicodeWidthChar;
+
+const HEADER: Style = Style::new().add_modifier(Modifier::BOLD);
+const DIM: Style = Style::new().add_modifier(Modifier::DIM);
+
+/// Delimiters tried when sniffing a `.csv` file
+const DELIMITERS: &[u8] = b",;\t|";
+
+/// Records looked at to sniff the delimiter and the header
+const SAMPLE_RECORDS: usize = 50;
+
+/// Columns wider than this are truncated
+const MAX_COLUMN_WIDTH: usize = 40;
+
+/// Drawn between columns
+const SEPARATOR: &str = " │ ";
+
+/// Rows of text laid out in columns: a delimited file, or a page of a
+/// database table
+pub struct Table {
+    /// The delimiter of a CSV or TSV file
+    pub delimiter: Option<u8>,
+    pub header: Option<Vec<String>>,
+    pub rows: Vec<Vec<String>>,
+    /// Display width of each column, capped at `MAX_COLUMN_WIDTH`
+    pub widths: Vec<usize>,
+    /// Columns whose values are all numbers, aligned to the right
+    numeric: Vec<bool>,
+}
+
+/// Whether a file is previewed as a table, by extension
+pub fn is_table(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv") || ext.eq_ignore_ascii_case("tsv"))
+}
+
+/// Parse `content` as CSV, or TSV for `.tsv` files. Returns None if it
+/// doesn't look like a table (a single column, or not parseable).
+pub fn parse(path: &Path, content: &str) -> Option<Table> {
+    let tsv = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("tsv"));
+    let delimiter = if tsv { b'\t' } else { sniff_delimiter(content)? };
+
+    let mut records = read(content, delimiter, usize::MAX).ok()?;
+    let columns = records.iter().map(Vec::len).max().unwrap_or(0);
+    if columns < 2 {
+        return None;
+    }
+    // Short records are padded so every row has every column
+    for record in &mut records {
+        record.resize(columns, String::new());
+    }
+
+    let header = has_header(&records).then(|| records.remove(0));
+    Some(Table {
+        delimiter: Some(delimiter),
+        ..Table::new(header, records)
+    })
+}
+
+fn read(content: &str, delimiter: u8, limit: usize) -> Result<Vec<Vec<String>>, csv::Error> {
+    csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content.as_bytes())
+        .records()
+        .take(limit)
+        .map(|record| record.map(|r| r.iter().map(str::to_string).collect()))
+        .collect()
+}
+
+/// The delimiter that splits the first records into the same number of
+/// fields most often, preferring more fields
+fn sniff_delimiter(content: &str) -> Option<u8> {
+    DELIMITERS
+        .iter()
+        .filter_map(|&delimiter| {
+            let records = read(content, delimiter, SAMPLE_RECORDS).ok()?;
+            let counts: Vec<usize> = records.iter().map(Vec::len).collect();
+            let fields = *counts.first()?;
+            if fields < 2 {
+                return None;
+            }
+            let consistent = counts.iter().filter(|&&n| n == fields).count();
+            Some(((consistent * 100 / counts.len(), fields), delimiter))
+        })
+        .max_by_key(|&(score, _)| score)
+        .map(|(_, delimiter)| delimiter)
+}
+
+/// Whether the first record names the columns, in the manner of Python's
+/// `csv.Sniffer`: each column votes for a header if its first value is text
+/// above numbers, or differs in length from values that all have the same
+/// length
+fn has_header(records: &[Vec<String>]) -> bool {
+    let Some((first, rest)) = records.split_first() else {
+        return false;
+    };
+    let sample = &rest[..rest.len().min(SAMPLE_RECORDS)];
+    if sample.is_empty() {
+        return false;
+    }
+    let mut votes = 0i32;
+    for (c, name) in first.iter().enumerate() {
+        let values: Vec<&str> = sample.iter().map(|r| r[c].trim()).filter(|v| !v.is_empty()).collect();
+        if values.is_empty() {
+            continue;
+        }
+        if values.iter().all(|v| is_number(v)) {
+            votes += if is_number(name.trim()) { -1 } else { 1 };
+        } else if values.iter().all(|v| v.len() == values[0].len()) {
+            votes += if name.trim().len() == values[0].len() { -1 } else { 1 };
+        }
+    }
+    votes > 0
+}
+
+fn is_number(value: &str) -> bool {
+    value.parse::<f64>().is_ok()
+}
+
+fn text_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Control characters are shown as spaces, so they take one column
+fn char_width(c: char) -> usize {
+    c.width().unwrap_or(1)
+}
+
+impl Table {
+    /// Lay out `rows`, which (like `header`) must all have the same number
+    /// of cells
+    pub fn new(header: Option<Vec<String>>, rows: Vec<Vec<String>>) -> Table {
+        let columns = header.as_ref().or(rows.first()).map_or(0, Vec::len);
+        let numeric = (0..columns)
+            .map(|c| {
+                let mut values = rows.iter().map(|r| r[c].trim()).filter(|v| !v.is_empty()).peekable();
+                values.peek().is_some() && values.all(is_number)
+            })
+            .collect();
+        let widths = (0..columns)
+            .map(|c| {
+                header
+                    .iter()
+                    .chain(&rows)
+                    .map(|r| text_width(&r[c]))
+                    .max()
+                    .unwrap_or(0)
+                    .clamp(1, MAX_COLUMN_WIDTH)
+            })
+            .collect();
+        Table {
+            delimiter: None,
+            header,
+            rows,
+            widths,
+            numeric,
+        }
+    }
+
+    pub fn column_count(&self) -> usize {
+        self.widths.len()
+    }
+
+    /// Rough memory use, for the preview cache
+    pub fn weight(&self) -> usize {
+        self.header
+            .iter()
+            .chain(&self.rows)
+            .flatten()
+            .map(|cell| cell.len() + std::mem::size_of::<String>())
+            .sum()
+    }
+
+    /// Size, delimiter and (when they don't all fit) the columns in view,
+    /// e.g. "120 rows × 9 columns, ';'-separated, columns 3–6"
+    pub fn summary(&self, columns: &[(usize, usize)]) -> String {
+        let mut summary = format!("{} rows × {} columns", self.rows.len(), self.column_count());
+        match self.delimiter {
+            Some(b'\t') => summary.push_str(", tab-separated"),
+            Some(d) => summary.push_str(&format!(", '{}'-separated", d as char)),
+            None => {}
+        }
+        summary.push_str(&self.column_range(columns));
+        summary
+    }
+
+    /// ", columns 3–6" when only some of the columns are in view
+    pub fn column_range(&self, columns: &[(usize, usize)]) -> String {
+        match (columns.first(), columns.last()) {
+            (Some(first), Some(last)) if columns.len() < self.column_count() => {
+                format!(", columns {}–{}", first.0 + 1, last.0 + 1)
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// The columns that fit in `width` starting at `first`, with the width
+    /// each is drawn at (the last one may be cut short)
+    pub fn visible_columns(&self, first: usize, width: usize) -> Vec<(usize, usize)> {
+        let mut columns = Vec::new();
+        let mut used = 0;
+        for (c, &column_width) in self.widths.iter().enumerate().skip(first) {
+            if !columns.is_empty() {
+                used += text_width(SEPARATOR);
+            }
+            if used >= width {
+                break;
+            }
+            let shown = column_width.min(width - used);
+            columns.push((c, shown));
+            used += shown;
+        }
+        columns
+    }
+
+    pub fn header_line(&self, columns: &[(usize, usize)]) -> Option<Line<'static>> {
+        Some(self.line(self.header.as_ref()?, columns, HEADER))
+    }
+
+    /// The rule under the header, crossing the column separators
+    pub fn rule(&self, columns: &[(usize, usize)]) -> Line<'static> {
+        let rule = columns
+            .iter()
+            .map(|&(_, width)| "─".repeat(width))
+            .collect::<Vec<_>>()
+            .join("─┼─");
+        Line::from(Span::styled(rule, DIM))
+    }
+
+    pub fn row_line(&self, row: usize, columns: &[(usize, usize)]) -> Line<'static> {
+        self.line(&self.rows[row], columns, Style::new())
+    }
+
+    fn line(&self, cells: &[String], columns: &[(usize, usize)], style: Style) -> Line<'static> {
+        let mut spans = Vec::new();
+        for (i, &(c, width)) in columns.iter().enumerate() {
+            if i > 0 {
+                spans.push(Span::styled(SEPARATOR, DIM));
+            }
+            let text = fit(&cells[c], width);
+            let padding = " ".repeat(width.saturating_sub(text_width(&text)));
+            let cell = if self.numeric[c] {
+                format!("{}{}", padding, text)
+            } else {
+                format!("{}{}", text, padding)
+            };
+            spans.push(Span::styled(cell, style));
+        }
+        Line::from(spans)
+    }
+}
+
+/// A cell's text on one line, with control characters (newlines, tabs,
+/// escapes) as spaces, cut to `width` columns with an ellipsis
+fn fit(text: &str, width: usize) -> String {
+    let text: String = text
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    if text_width(&text) <= width {
+        return text;
+    }
+    let mut fitted = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = char_width(c);
+        if used + w + 1 > width {
+            break;
+        }
+        fitted.push(c);
+        used += w;
+    }
+    if width > 0 {
+        fitted.push('…');
+    }
+    fitted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(line: &Line) -> String {
+        line.spans.iter().map(|span| span.content.as_ref()).collect()
+    }
+
+    #[test]
+    fn control_characters_in_over_wide_cells_are_spaces() {
+        let rows = vec![
+            vec!["a\x01b\x1b[31mred".repeat(10), "1".to_string()],
+            vec!["short".to_string(), "22".to_string()],
+        ];
+        let table = Table::new(None, rows);
+        let columns = table.visible_columns(0, 30);
+        let line = cells(&table.row_line(0, &columns));
+        assert!(!line.chars().any(char::is_control), "{:?}", line);
+        assert_eq!(text_width(&line), 30);
+        assert!(line.starts_with("a b [31mred"));
+        assert_eq!(cells(&table.row_line(1, &columns)).len(), 30);
+    }
+
+    #[test]
+    fn sniffs_the_delimiter_and_header() {
+        let table = parse(Path::new("t.csv"), "name;age\nada;36\nalan;41\n").unwrap();
+        assert_eq!(table.delimiter, Some(b';'));
+        assert_eq!(table.header, Some(vec!["name".to_string(), "age".to_string()]));
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(table.summary(&table.visible_columns(0, 80)), "2 rows × 2 columns, ';'-separated");
+
+        let table = parse(Path::new("t.csv"), "1,2\n3,4\n").unwrap();
+        assert_eq!(table.header, None);
+        assert!(parse(Path::new("t.csv"), "just one column\nper line\n").is_none());
+    }
+}
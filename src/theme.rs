@@ -0,0 +1,196 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use syntect::highlighting::{Theme, ThemeSet};
+
+use crate::config::{self, PreviewConfig};
+
+/// `preview.theme` value that picks the theme automatically
+pub const AUTO: &str = "auto";
+
+pub const DEFAULT_DARK: &str = "base16-ocean.dark";
+pub const DEFAULT_LIGHT: &str = "base16-ocean.light";
+
+/// How long to wait for the terminal to report its background color
+#[cfg(unix)]
+const QUERY_TIMEOUT_MS: i32 = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Background {
+    Light,
+    Dark,
+}
+
+/// `$XDG_CONFIG_HOME/browse/themes`, where extra `.tmTheme` files go
+pub fn user_theme_dir() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("themes"))
+}
+
+/// Load every `.tmTheme` file in `dir`, named after the file. Returns the
+/// themes and a message for each file that failed to load.
+pub fn load_user_themes(dir: &Path) -> (Vec<(String, Theme)>, Vec<String>) {
+    let mut themes = Vec::new();
+    let mut errors = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return (themes, errors);
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("tmtheme")))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match ThemeSet::get_theme(&path) {
+            Ok(theme) => themes.push((name, theme)),
+            Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    (themes, errors)
+}
+
+/// Where the theme chosen in the theme picker is remembered
+fn saved_theme_path() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("browse").join("theme"))
+}
+
+fn load_saved() -> Option<String> {
+    let saved = fs::read_to_string(saved_theme_path()?).ok()?;
+    let name = saved.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Remember `name` as the theme to use when `preview.theme` is "auto"
+pub fn save(name: &str) -> io::Result<()> {
+    let path = saved_theme_path()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no state directory"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, format!("{}\n", name))
+}
+
+/// The theme `preview.theme` stands for. "auto" means the theme last chosen
+/// in the theme picker (if `exists` still knows it), otherwise the light or
+/// dark theme matching the terminal background.
+pub fn resolve(config: &PreviewConfig, exists: impl Fn(&str) -> bool) -> String {
+    if config.theme != AUTO {
+        return config.theme.clone();
+    }
+    if let Some(saved) = load_saved().filter(|name| exists(name)) {
+        return saved;
+    }
+    match background() {
+        Some(Background::Light) => config.light_theme.clone(),
+        _ => config.dark_theme.clone(),
+    }
+}
+
+/// The terminal's background, detected on the first call. Call it before
+/// the UI starts reading input, or the terminal's reply gets mixed in.
+pub fn background() -> Option<Background> {
+    static DETECTED: OnceLock<Option<Background>> = OnceLock::new();
+    *DETECTED.get_or_init(|| query_background().or_else(colorfgbg))
+}
+
+/// `COLORFGBG` as set by rxvt, Konsole and others, e.g. "15;0": the last
+/// field is the palette index of the background
+fn colorfgbg() -> Option<Background> {
+    let value = std::env::var("COLORFGBG").ok()?;
+    let index: u8 = value.rsplit(';').next()?.parse().ok()?;
+    // White, light gray and the bright colors
+    let light = index == 7 || (9..=15).contains(&index);
+    Some(if light { Background::Light } else { Background::Dark })
+}
+
+/// Ask the terminal for its background color with OSC 11. A device
+/// attributes query follows it: every terminal answers that one, so a
+/// terminal that ignores OSC 11 doesn't make us wait for the timeout.
+#[cfg(unix)]
+fn query_background() -> Option<Background> {
+    use std::io::Write;
+
+    let mut tty = fs::OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+    let was_raw = crossterm::terminal::is_raw_mode_enabled().unwrap_or(false);
+    crossterm::terminal::enable_raw_mode().ok()?;
+    let reply = tty
+        .write_all(b"\x1b]11;?\x1b\\\x1b[c")
+        .and_then(|_| tty.flush())
+        .ok()
+        .and_then(|_| read_reply(&tty));
+    if !was_raw {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+    parse_background(&reply?)
+}
+
+#[cfg(not(unix))]
+fn query_background() -> Option<Background> {
+    None
+}
+
+/// Read until the device attributes reply (`ESC [ ? ... c`) arrives
+#[cfg(unix)]
+fn read_reply(tty: &fs::File) -> Option<String> {
+    use std::io::Read;
+    use std::os::fd::AsRawFd;
+
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 256];
+    loop {
+        let mut fd = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `fd` is a valid pollfd for the duration of the call
+        if unsafe { libc::poll(&mut fd, 1, QUERY_TIMEOUT_MS) } <= 0 {
+            break;
+        }
+        let n = (&mut &*tty).read(&mut buf).ok()?;
+        if n == 0 {
+            break;
+        }
+        reply.extend_from_slice(&buf[..n]);
+        if let Some(start) = find(&reply, b"\x1b[?")
+            && reply[start..].contains(&b'c')
+        {
+            break;
+        }
+    }
+    Some(String::from_utf8_lossy(&reply).into_owned())
+}
+
+#[cfg(unix)]
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Light or dark from an OSC 11 reply like `ESC ] 11 ; rgb:ffff/ffff/dddd ESC \`
+#[cfg_attr(not(unix), allow(dead_code))]
+fn parse_background(reply: &str) -> Option<Background> {
+    let start = reply.find("]11;rgb:")? + "]11;rgb:".len();
+    let rest = &reply[start..];
+    let end = rest.find(['\x1b', '\x07']).unwrap_or(rest.len());
+    let channels: Vec<f64> = rest[..end]
+        .split('/')
+        .map(|hex| {
+            let value = u32::from_str_radix(hex, 16).ok()?;
+            let max = (1u32 << (4 * hex.len().min(4))) - 1;
+            Some(value as f64 / max as f64)
+        })
+        .collect::<Option<_>>()?;
+    let [r, g, b] = channels[..] else {
+        return None;
+    };
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    Some(if luminance > 0.5 { Background::Light } else { Background::Dark })
+}
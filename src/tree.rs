@@ -1,6 +1,12 @@
+use std::cmp::Ordering;
 use std::collections::HashSet;
-use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use ignore::WalkBuilder;
+use serde::Deserialize;
+
+use crate::vfs::{DirEntry, Vfs};
 
 #[derive(Clone)]
 pub struct TreeNode {
@@ -8,6 +14,9 @@ pub struct TreeNode {
     pub path: PathBuf,
     pub is_directory: bool,
     pub is_symlink: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub is_archive: bool, // expands into its contents like a directory
     pub children: Option<Vec<TreeNode>>, // None = not yet loaded
 }
 
@@ -19,87 +28,194 @@ pub struct VisibleRow {
     pub path: PathBuf,
     pub is_directory: bool,
     pub is_symlink: bool,
+    pub is_archive: bool,
     pub depth: usize,
     pub is_expanded: bool,
 }
 
+impl VisibleRow {
+    /// Directories and archives can be expanded
+    pub fn is_expandable(&self) -> bool {
+        self.is_directory || self.is_archive
+    }
+}
+
+/// Order of entries within a directory (directories always come first)
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortMode {
+    /// Case-insensitive alphabetical
+    #[default]
+    Name,
+    /// Largest first
+    Size,
+    /// Most recently modified first
+    #[serde(alias = "mtime", alias = "time")]
+    Modified,
+    /// By extension, then by name
+    #[serde(alias = "ext")]
+    Extension,
+}
+
+impl SortMode {
+    pub const ALL: [SortMode; 4] = [
+        SortMode::Name,
+        SortMode::Size,
+        SortMode::Modified,
+        SortMode::Extension,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortMode::Name => "name",
+            SortMode::Size => "size",
+            SortMode::Modified => "modified",
+            SortMode::Extension => "extension",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<SortMode> {
+        match s {
+            "name" => Some(SortMode::Name),
+            "size" => Some(SortMode::Size),
+            "modified" | "mtime" | "time" => Some(SortMode::Modified),
+            "extension" | "ext" => Some(SortMode::Extension),
+            _ => None,
+        }
+    }
+}
+
+/// Settings that control which entries are listed and in what order
+#[derive(Clone, Default)]
+pub struct TreeOptions {
+    pub show_hidden: bool,
+    /// Skip entries matched by .gitignore, .ignore and git exclude files
+    pub respect_ignore: bool,
+    pub sort: SortMode,
+    /// A path revealed on request, listed with its ancestors even if they're
+    /// hidden or ignored
+    pub revealed: Option<PathBuf>,
+}
+
+impl TreeOptions {
+    /// Whether `path` is the revealed path or one of its ancestors
+    fn is_revealed(&self, path: &Path) -> bool {
+        self.revealed.as_deref().is_some_and(|revealed| revealed.starts_with(path))
+    }
+}
+
 /// Read one level of a directory, returning TreeNodes with children = None
-pub fn build_tree(dir_path: &Path) -> Vec<TreeNode> {
-    let entries = match fs::read_dir(dir_path) {
+pub fn build_tree(vfs: &dyn Vfs, dir_path: &Path, options: &TreeOptions) -> Vec<TreeNode> {
+    let entries = match vfs.read_dir(dir_path) {
         Ok(entries) => entries,
         Err(_) => return Vec::new(),
     };
+    // Ignore files only exist on the local disk
+    let allowed = (options.respect_ignore && vfs.is_local(dir_path))
+        .then(|| unignored_paths(dir_path));
 
-    let mut nodes: Vec<TreeNode> = Vec::new();
-
-    for entry in entries.flatten() {
-        let name = entry.file_name().to_string_lossy().to_string();
-        let path = entry.path();
-
-        let metadata = entry.metadata();
-        let is_symlink = entry
-            .file_type()
-            .map(|ft| ft.is_symlink())
-            .unwrap_or(false);
-
-        let is_directory = if is_symlink {
-            // Follow symlink to determine if it points to a directory
-            fs::metadata(&path)
-                .map(|m| m.is_dir())
-                .unwrap_or(false)
-        } else {
-            metadata.map(|m| m.is_dir()).unwrap_or(false)
-        };
+    let mut nodes: Vec<TreeNode> = entries
+        .into_iter()
+        .filter(|entry| {
+            allowed.as_ref().is_none_or(|allowed| allowed.contains(&entry.path))
+                || options.is_revealed(&entry.path)
+        })
+        .map(|entry| make_node(vfs, entry))
+        .collect();
 
-        nodes.push(TreeNode {
-            name,
-            path,
-            is_directory,
-            is_symlink,
-            children: None,
-        });
+    sort_nodes(&mut nodes, options.sort);
+    nodes
+}
+
+/// Paths in a directory that aren't ignored, according to the `ignore`
+/// crate so gitignore rules from the directory and its parents are
+/// honoured. Hidden filtering is left to `flatten_tree` so toggling it
+/// doesn't require a reload.
+fn unignored_paths(dir_path: &Path) -> HashSet<PathBuf> {
+    WalkBuilder::new(dir_path)
+        .max_depth(Some(1))
+        .hidden(false)
+        .follow_links(false)
+        .build()
+        .flatten()
+        .filter(|entry| entry.depth() == 1)
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+fn make_node(vfs: &dyn Vfs, entry: DirEntry) -> TreeNode {
+    // Follow symlinks so links to directories can be expanded
+    let metadata = vfs.metadata(&entry.path).unwrap_or_default();
+
+    TreeNode {
+        name: entry.name,
+        path: entry.path,
+        is_directory: metadata.is_dir,
+        is_symlink: entry.is_symlink,
+        size: metadata.len,
+        modified: metadata.modified,
+        is_archive: metadata.is_archive,
+        children: None,
     }
+}
 
-    // Sort: directories first, then case-insensitive alphabetical
+/// Sort: directories first, then by the chosen mode with the
+/// case-insensitive name as a tie-breaker
+fn sort_nodes(nodes: &mut [TreeNode], mode: SortMode) {
     nodes.sort_by(|a, b| {
         if a.is_directory != b.is_directory {
             return if a.is_directory {
-                std::cmp::Ordering::Less
+                Ordering::Less
             } else {
-                std::cmp::Ordering::Greater
+                Ordering::Greater
             };
         }
-        a.name.to_lowercase().cmp(&b.name.to_lowercase())
+        let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase());
+        match mode {
+            SortMode::Name => by_name(),
+            SortMode::Size => b.size.cmp(&a.size).then_with(by_name),
+            SortMode::Modified => b.modified.cmp(&a.modified).then_with(by_name),
+            SortMode::Extension => extension_of(&a.name)
+                .cmp(&extension_of(&b.name))
+                .then_with(by_name),
+        }
     });
+}
 
-    nodes
+fn extension_of(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
 }
 
 /// Load children for a node in place
-pub fn load_children(node: &mut TreeNode) {
-    if !node.is_directory {
+pub fn load_children(vfs: &dyn Vfs, node: &mut TreeNode, options: &TreeOptions) {
+    if !node.is_directory && !node.is_archive {
         return;
     }
-    node.children = Some(build_tree(&node.path));
+    node.children = Some(build_tree(vfs, &node.path, options));
 }
 
 const MAX_TREE_DEPTH: usize = 50;
 
 /// Recursively flatten expanded tree into visible rows
 pub fn flatten_tree(
-    nodes: &mut Vec<TreeNode>,
+    vfs: &dyn Vfs,
+    nodes: &mut [TreeNode],
     expanded: &HashSet<PathBuf>,
-    show_hidden: bool,
+    options: &TreeOptions,
 ) -> Vec<VisibleRow> {
     let mut rows = Vec::new();
-    flatten_recursive(nodes, expanded, show_hidden, 0, &mut Vec::new(), &mut rows);
+    flatten_recursive(vfs, nodes, expanded, options, 0, &mut Vec::new(), &mut rows);
     rows
 }
 
 fn flatten_recursive(
-    nodes: &mut Vec<TreeNode>,
+    vfs: &dyn Vfs,
+    nodes: &mut [TreeNode],
     expanded: &HashSet<PathBuf>,
-    show_hidden: bool,
+    options: &TreeOptions,
     depth: usize,
     idx_path: &mut Vec<usize>,
     rows: &mut Vec<VisibleRow>,
@@ -108,13 +224,12 @@ fn flatten_recursive(
         return;
     }
 
-    for i in 0..nodes.len() {
-        let node = &nodes[i];
-        if !show_hidden && node.name.starts_with('.') {
+    for (i, node) in nodes.iter_mut().enumerate() {
+        if !options.show_hidden && node.name.starts_with('.') && !options.is_revealed(&node.path) {
             continue;
         }
 
-        let is_expanded = node.is_directory && expanded.contains(&node.path);
+        let is_expanded = (node.is_directory || node.is_archive) && expanded.contains(&node.path);
 
         idx_path.push(i);
         rows.push(VisibleRow {
@@ -123,15 +238,16 @@ fn flatten_recursive(
             path: node.path.clone(),
             is_directory: node.is_directory,
             is_symlink: node.is_symlink,
+            is_archive: node.is_archive,
             depth,
             is_expanded,
         });
 
         if is_expanded {
-            // Always reload children from disk to reflect filesystem changes
-            load_children(&mut nodes[i]);
-            if let Some(ref mut children) = nodes[i].children {
-                flatten_recursive(children, expanded, show_hidden, depth + 1, idx_path, rows);
+            // Always reload children to reflect filesystem changes
+            load_children(vfs, node, options);
+            if let Some(ref mut children) = node.children {
+                flatten_recursive(vfs, children, expanded, options, depth + 1, idx_path, rows);
             }
         }
 
@@ -139,6 +255,17 @@ fn flatten_recursive(
     }
 }
 
+/// Pick a root directory for browsing a single file: the nearest ancestor
+/// containing `.git`, or the file's parent directory if there is none
+pub fn find_project_root(file_path: &Path) -> PathBuf {
+    let parent = file_path.parent().unwrap_or(file_path);
+    parent
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(parent)
+        .to_path_buf()
+}
+
 /// Walk backward from index to find the nearest row with depth < current
 pub fn find_parent_row(rows: &[VisibleRow], index: usize) -> usize {
     let current_depth = rows.get(index).map(|r| r.depth).unwrap_or(0);
@@ -152,3 +279,103 @@ pub fn find_parent_row(rows: &[VisibleRow], index: usize) -> usize {
     }
     index
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemoryVfs;
+
+    fn sample() -> MemoryVfs {
+        let mut vfs = MemoryVfs::new();
+        vfs.add_file("/p/README.md", "# Readme");
+        vfs.add_file("/p/build.rs", "fn main() {}\n// a longer file");
+        vfs.add_file("/p/.env", "KEY=1");
+        vfs.add_file("/p/src/main.rs", "fn main() {}");
+        vfs.add_file("/p/src/lib.rs", "");
+        vfs.add_dir("/p/assets");
+        vfs
+    }
+
+    fn names(rows: &[VisibleRow]) -> Vec<String> {
+        rows.iter().map(|r| format!("{}{}", "  ".repeat(r.depth), r.name)).collect()
+    }
+
+    #[test]
+    fn directories_come_first_then_names() {
+        let vfs = sample();
+        let mut nodes = build_tree(&vfs, Path::new("/p"), &TreeOptions::default());
+        let rows = flatten_tree(&vfs, &mut nodes, &HashSet::new(), &TreeOptions::default());
+        assert_eq!(names(&rows), ["assets", "src", "build.rs", "README.md"]);
+        assert!(rows[0].is_expandable() && !rows[0].is_expanded);
+    }
+
+    #[test]
+    fn hidden_files_are_shown_on_request() {
+        let vfs = sample();
+        let options = TreeOptions {
+            show_hidden: true,
+            ..TreeOptions::default()
+        };
+        let mut nodes = build_tree(&vfs, Path::new("/p"), &options);
+        let rows = flatten_tree(&vfs, &mut nodes, &HashSet::new(), &options);
+        assert_eq!(names(&rows), ["assets", "src", ".env", "build.rs", "README.md"]);
+    }
+
+    #[test]
+    fn a_revealed_path_is_listed_with_its_hidden_ancestors() {
+        let mut vfs = sample();
+        vfs.add_file("/p/.config/app.toml", "");
+        vfs.add_file("/p/.config/.secret", "");
+        let options = TreeOptions {
+            revealed: Some(PathBuf::from("/p/.config/app.toml")),
+            ..TreeOptions::default()
+        };
+        let expanded = HashSet::from([PathBuf::from("/p/.config")]);
+        let mut nodes = build_tree(&vfs, Path::new("/p"), &options);
+        let rows = flatten_tree(&vfs, &mut nodes, &expanded, &options);
+        assert_eq!(names(&rows), [".config", "  app.toml", "assets", "src", "build.rs", "README.md"]);
+    }
+
+    #[test]
+    fn expanded_directories_list_their_children() {
+        let vfs = sample();
+        let options = TreeOptions::default();
+        let expanded = HashSet::from([PathBuf::from("/p/src")]);
+        let mut nodes = build_tree(&vfs, Path::new("/p"), &options);
+        let rows = flatten_tree(&vfs, &mut nodes, &expanded, &options);
+        assert_eq!(names(&rows), ["assets", "src", "  lib.rs", "  main.rs", "build.rs", "README.md"]);
+        assert_eq!(rows[3].path, Path::new("/p/src/main.rs"));
+        assert_eq!(find_parent_row(&rows, 3), 1);
+    }
+
+    #[test]
+    fn sorting_by_size_puts_larger_files_first() {
+        let vfs = sample();
+        let options = TreeOptions {
+            sort: SortMode::Size,
+            ..TreeOptions::default()
+        };
+        let mut nodes = build_tree(&vfs, Path::new("/p"), &options);
+        let rows = flatten_tree(&vfs, &mut nodes, &HashSet::new(), &options);
+        assert_eq!(names(&rows), ["assets", "src", "build.rs", "README.md"]);
+
+        let mut vfs = vfs;
+        vfs.add_file("/p/README.md", "x".repeat(100));
+        let mut nodes = build_tree(&vfs, Path::new("/p"), &options);
+        let rows = flatten_tree(&vfs, &mut nodes, &HashSet::new(), &options);
+        assert_eq!(names(&rows), ["assets", "src", "README.md", "build.rs"]);
+    }
+
+    #[test]
+    fn ties_are_broken_by_name() {
+        let mut vfs = sample();
+        vfs.set_modified(SystemTime::UNIX_EPOCH);
+        let options = TreeOptions {
+            sort: SortMode::Modified,
+            ..TreeOptions::default()
+        };
+        let mut nodes = build_tree(&vfs, Path::new("/p"), &options);
+        let rows = flatten_tree(&vfs, &mut nodes, &HashSet::new(), &options);
+        assert_eq!(names(&rows), ["assets", "src", "build.rs", "README.md"]);
+    }
+}
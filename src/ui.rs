@@ -1,30 +1,67 @@
-use crate::ansi::parse_ansi_line;
+use std::path::Path;
+
 use crate::app::App;
-use crate::preview::PreviewContent;
-use ratatui::layout::{Constraint, Direction, Layout};
-use ratatui::style::{Color, Modifier, Style};
+use crate::binary;
+use crate::graphics::{self, ImageProtocol};
+use crate::keymap::{format_sequence, Action};
+use crate::markdown::Document;
+use crate::preview::{format_size, PreviewContent};
+use crate::stream::LineIndex;
+use crate::sqlite::Database;
+use crate::structured::Tree;
+use crate::table::Table;
+use image::RgbaImage;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
 use ratatui::Frame;
 
-pub fn draw(f: &mut Frame, app: &App) {
+/// What the caller draws on top of the frame itself
+#[derive(Default)]
+pub struct Overlays {
+    pub image: Option<Rect>, // area reserved for an image drawn with a graphics protocol
+    pub links: Vec<(Rect, String)>, // visible markdown links, for terminal hyperlinks
+}
+
+/// Draw the whole UI. Returns what the caller has to draw on top of it.
+pub fn draw(f: &mut Frame, app: &App) -> Overlays {
     let area = f.area();
+    let mut overlays = Overlays::default();
 
-    // Split into left (35%) and right (65%)
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage(35),
-            Constraint::Percentage(65),
-        ])
-        .split(area);
-
-    draw_tree(f, app, chunks[0]);
-    draw_preview(f, app, chunks[1]);
+    if !app.show_preview {
+        draw_tree(f, app, area);
+    } else {
+        // Split into tree (left) and preview (right), using the same width
+        // calculation as mouse hit-testing
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Length(app.tree_width(area.width)),
+                Constraint::Min(0),
+            ])
+            .split(area);
+
+        draw_tree(f, app, chunks[0]);
+        overlays = draw_preview(f, app, chunks[1]);
+    }
+
+    // Popups would be covered by the image and links, so those are hidden
+    // while they're open
+    if app.help.is_some() {
+        draw_help(f, app, area);
+        overlays = Overlays::default();
+    }
+    if app.palette.is_some() {
+        draw_palette(f, app, area);
+        overlays = Overlays::default();
+    }
+    overlays
 }
 
 fn draw_tree(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
     let rows = &app.visible_rows;
+    let colors = &app.config.colors;
 
     // Reserve 1 line for status bar at the bottom
     let list_height = area.height.saturating_sub(1) as usize;
@@ -45,14 +82,14 @@ fn draw_tree(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
     lines.push(Line::from(Span::styled(
         header,
         Style::default()
-            .fg(Color::Blue)
+            .fg(colors.header)
             .add_modifier(Modifier::BOLD),
     )));
 
     // Separator
     lines.push(Line::from(Span::styled(
         "─".repeat(area.width as usize),
-        Style::default().fg(Color::DarkGray),
+        Style::default().fg(colors.border),
     )));
 
     // File entries
@@ -60,12 +97,11 @@ fn draw_tree(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
     let entries_to_show = list_height.saturating_sub(2);
     let visible_end = (scroll_offset + entries_to_show).min(rows.len());
 
-    for i in scroll_offset..visible_end {
-        let row = &rows[i];
+    for (i, row) in rows.iter().enumerate().take(visible_end).skip(scroll_offset) {
         let is_selected = i == app.selected_index;
 
         let indent = "  ".repeat(row.depth);
-        let icon = if row.is_directory {
+        let icon = if row.is_expandable() {
             if row.is_expanded {
                 "▼ "
             } else {
@@ -80,10 +116,10 @@ fn draw_tree(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
 
         let style = if is_selected {
             Style::default()
-                .fg(Color::Green)
+                .fg(colors.selected)
                 .add_modifier(Modifier::BOLD | Modifier::REVERSED)
         } else if row.is_directory {
-            Style::default().fg(Color::Cyan)
+            Style::default().fg(colors.directory)
         } else {
             Style::default()
         };
@@ -97,46 +133,513 @@ fn draw_tree(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
     }
 
     // Status bar
-    let status = format!(
-        " {} items | j/k:nav l:expand h:collapse y:copy r:refresh q:quit",
-        rows.len()
-    );
+    let status = match (&app.status_message, app.focused_link()) {
+        _ if !app.pending_keys.is_empty() => format!(" {}", format_sequence(&app.pending_keys)),
+        (Some(message), _) => format!(" {}", message),
+        (None, Some(url)) => format!(" link: {}", url),
+        (None, None) if app.show_debug => format!(" {}", app.debug_info()),
+        (None, None) => {
+            let hints: Vec<String> = [(Action::ShowHelp, "help"), (Action::Quit, "quit")]
+                .iter()
+                .filter_map(|&(action, label)| {
+                    let keys = app.config.keymap.keys_for(action);
+                    keys.first().map(|key| format!("{}:{}", key, label))
+                })
+                .collect();
+            let following = if app.follow.is_some() { "following | " } else { "" };
+            let section = app
+                .current_section()
+                .map(|s| format!("§ {} | ", s))
+                .or_else(|| app.node_path().map(|p| format!("{} | ", p)))
+                .unwrap_or_default();
+            format!(" {}{}{} items | {}", section, following, rows.len(), hints.join(" "))
+        }
+    };
     lines.push(Line::from(Span::styled(
         status,
-        Style::default().fg(Color::DarkGray),
+        Style::default().fg(colors.status),
     )));
 
     let paragraph = Paragraph::new(lines);
     f.render_widget(paragraph, area);
 }
 
-fn draw_preview(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
-    let block = Block::default().borders(Borders::LEFT);
+fn draw_preview(f: &mut Frame, app: &App, area: ratatui::layout::Rect) -> Overlays {
+    let block = Block::default()
+        .borders(Borders::LEFT)
+        .border_style(Style::default().fg(app.config.colors.border));
     let inner = block.inner(area);
     f.render_widget(block, area);
 
     let (content, _total_lines) = &app.preview_cache;
 
-    let text = match content {
-        PreviewContent::Text(s) => s.clone(),
-        PreviewContent::Directory(s) => s.clone(),
-        PreviewContent::Binary(s) => s.clone(),
-        PreviewContent::Empty => "(empty file)".to_string(),
-        PreviewContent::Error(s) => s.clone(),
+    let height = inner.height as usize;
+    let lines: Vec<Line> = match content {
+        PreviewContent::Text(lines) => {
+            let scroll = app.preview_scroll.min(lines.len().saturating_sub(1));
+            lines.iter().skip(scroll).take(height).cloned().collect()
+        }
+        PreviewContent::Elf(info) => {
+            let scroll = app.preview_scroll.min(info.lines.len().saturating_sub(1));
+            info.lines.iter().skip(scroll).take(height).cloned().collect()
+        }
+        PreviewContent::Code(code) => {
+            let scroll = app.preview_scroll.min(code.line_count().saturating_sub(1));
+            code.lines(scroll, height)
+        }
+        PreviewContent::Markdown(document) => return draw_markdown(f, app, inner, document),
+        PreviewContent::Table(table) => {
+            draw_table(f, app, inner, table);
+            return Overlays::default();
+        }
+        PreviewContent::Database(database) => {
+            match app.db_table {
+                Some(i) => draw_database_table(f, app, inner, database, i),
+                None => draw_database(f, app, inner, database),
+            }
+            return Overlays::default();
+        }
+        PreviewContent::Structured(tree) => {
+            draw_structured(f, app, inner, tree);
+            return Overlays::default();
+        }
+        PreviewContent::Directory(s) => vec![Line::from(s.as_str())],
+        PreviewContent::Hex { path, size, header, bytes } => {
+            draw_hex(f, app, inner, path, header, bytes.as_deref(), binary::hex_rows(*size));
+            return Overlays::default();
+        }
+        PreviewContent::Image { header, image, .. } => {
+            return Overlays {
+                image: draw_image(f, app, inner, header, image),
+                ..Overlays::default()
+            };
+        }
+        PreviewContent::Stream(index) => {
+            draw_stream(f, app, inner, index);
+            return Overlays::default();
+        }
+        PreviewContent::Loading => vec![Line::from("Loading…")],
+        PreviewContent::Empty => vec![Line::from("(empty file)")],
+        PreviewContent::Error(s) => vec![Line::from(s.as_str())],
     };
 
-    // Split into lines and apply scroll offset
-    let all_lines: Vec<&str> = text.lines().collect();
-    let scroll = app.preview_scroll.min(all_lines.len().saturating_sub(1));
-    let visible_lines = &all_lines[scroll..];
+    let paragraph = Paragraph::new(lines);
+    f.render_widget(paragraph, inner);
+    Overlays::default()
+}
+
+/// A summary line, the header row kept in place, then the rows scrolled
+/// to, starting at the first column scrolled to
+fn draw_table(f: &mut Frame, app: &App, area: Rect, table: &Table) {
+    let columns = table.visible_columns(app.table_column, area.width as usize);
+    let mut lines = vec![Line::from(Span::styled(
+        table.summary(&columns),
+        Style::default().fg(app.config.colors.status),
+    ))];
+    if let Some(header) = table.header_line(&columns) {
+        lines.push(header);
+        lines.push(table.rule(&columns));
+    }
+
+    let height = (area.height as usize).saturating_sub(lines.len());
+    let scroll = app.preview_scroll.min(table.rows.len().saturating_sub(1));
+    let end = (scroll + height).min(table.rows.len());
+    lines.extend((scroll..end).map(|row| table.row_line(row, &columns)));
+    f.render_widget(Paragraph::new(lines), area);
+}
+
+/// The schema listing, with the selected object's heading highlighted
+fn draw_database(f: &mut Frame, app: &App, area: Rect, database: &Database) {
+    let scroll = app.preview_scroll.min(database.lines.len().saturating_sub(1));
+    let lines: Vec<Line> = database.lines.iter().skip(scroll).take(area.height as usize).cloned().collect();
+    f.render_widget(Paragraph::new(lines), area);
+
+    if let Some(object) = database.objects.get(app.db_cursor)
+        && object.line >= scroll
+        && object.line < scroll + area.height as usize
+    {
+        let rect = Rect {
+            y: area.y + (object.line - scroll) as u16,
+            height: 1,
+            ..area
+        };
+        f.buffer_mut().set_style(rect, Style::default().add_modifier(Modifier::REVERSED));
+    }
+}
+
+/// The page of a table's rows that fits, as fetched by `App::fetch_db_page`
+fn draw_database_table(f: &mut Frame, app: &App, area: Rect, database: &Database, index: usize) {
+    let object = &database.objects[index];
+    let status = Style::default().fg(app.config.colors.status);
 
-    // Parse ANSI escape sequences into styled ratatui spans
-    let lines: Vec<Line> = visible_lines
+    // The page is read between frames, when the table or the scroll
+    // position changes
+    let Some(page) = app.db_page.as_ref().filter(|p| p.table == index) else {
+        return;
+    };
+    let table = match &page.rows {
+        Ok(table) => table,
+        Err(e) => {
+            f.render_widget(Paragraph::new(format!("Error: {}", e)), area);
+            return;
+        }
+    };
+    let count = table.rows.len();
+    let columns = table.visible_columns(app.table_column, area.width as usize);
+    let rows = match object.rows {
+        Some(n) => format!("{} rows", n),
+        None => "? rows".to_string(),
+    };
+    let range = if count > 0 {
+        format!(", showing {}–{}", page.offset + 1, page.offset + count)
+    } else {
+        String::new()
+    };
+    let summary = format!(
+        "{} {}: {} × {} columns{}{}",
+        object.kind,
+        object.name,
+        rows,
+        table.column_count(),
+        range,
+        table.column_range(&columns)
+    );
+
+    let mut lines = vec![Line::from(Span::styled(summary, status))];
+    lines.extend(table.header_line(&columns));
+    lines.push(table.rule(&columns));
+    lines.extend((0..count).map(|row| table.row_line(row, &columns)));
+    f.render_widget(Paragraph::new(lines), area);
+}
+
+/// The visible rows of a JSON/YAML/TOML tree, with the selected node
+/// highlighted
+fn draw_structured(f: &mut Frame, app: &App, area: Rect, tree: &Tree) {
+    let rows = tree.rows(&app.folded);
+    let scroll = app.preview_scroll.min(rows.len().saturating_sub(1));
+    let lines: Vec<Line> = rows
         .iter()
-        .take(inner.height as usize)
-        .map(|l| parse_ansi_line(l))
+        .skip(scroll)
+        .take(area.height as usize)
+        .map(|&i| tree.line(i, app.folded.contains(&i)))
         .collect();
+    f.render_widget(Paragraph::new(lines), area);
 
-    let paragraph = Paragraph::new(lines);
-    f.render_widget(paragraph, inner);
+    if let Some(row) = rows.iter().position(|&i| i == app.node_cursor)
+        && row >= scroll
+        && row < scroll + area.height as usize
+    {
+        let rect = Rect {
+            y: area.y + (row - scroll) as u16,
+            height: 1,
+            ..area
+        };
+        f.buffer_mut().set_style(rect, Style::default().add_modifier(Modifier::REVERSED));
+    }
+}
+
+/// Rendered markdown with the selected link highlighted. Returns where each
+/// visible link ended up.
+fn draw_markdown(f: &mut Frame, app: &App, area: Rect, document: &Document) -> Overlays {
+    let scroll = app.preview_scroll.min(document.lines.len().saturating_sub(1));
+    let height = area.height as usize;
+    let lines: Vec<Line> = document.lines.iter().skip(scroll).take(height).cloned().collect();
+    f.render_widget(Paragraph::new(lines), area);
+
+    let mut links = Vec::new();
+    for (i, link) in document.links.iter().enumerate() {
+        for region in &link.regions {
+            if region.line < scroll || region.line >= scroll + height {
+                continue;
+            }
+            let column = (region.column as u16).min(area.width);
+            let rect = Rect {
+                x: area.x + column,
+                y: area.y + (region.line - scroll) as u16,
+                width: (region.width as u16).min(area.width - column),
+                height: 1,
+            };
+            if app.link_focus == Some(i) {
+                f.buffer_mut().set_style(rect, Style::default().add_modifier(Modifier::REVERSED));
+            }
+            links.push((rect, link.url.clone()));
+        }
+    }
+    Overlays { image: None, links }
+}
+
+/// The visible window of a large file, with the position on the last line
+fn draw_stream(f: &mut Frame, app: &App, area: Rect, index: &LineIndex) {
+    let rows = area.height.saturating_sub(1) as usize;
+    let total = index.line_count();
+    let scroll = app.preview_scroll.min(total.saturating_sub(1));
+
+    let mut lines: Vec<Line> = match app.stream_lines(index, scroll, rows) {
+        Ok(lines) => lines,
+        Err(e) => vec![Line::from(e)],
+    };
+    lines.resize(rows, Line::from(""));
+
+    let mut position = format!(
+        "lines {}-{} of {}",
+        scroll + 1,
+        (scroll + rows).min(total),
+        total
+    );
+    if !index.is_complete() {
+        position.push_str(&format!("+ (indexing {}%)", index.progress()));
+    }
+    position.push_str(&format!(", {}", format_size(index.size())));
+    lines.push(Line::from(Span::styled(
+        position,
+        Style::default().fg(app.config.colors.status),
+    )));
+
+    f.render_widget(Paragraph::new(lines), area);
+}
+
+/// Header line, then the image as half blocks, or blank space for the
+/// caller to draw it in with a graphics protocol
+fn draw_image(f: &mut Frame, app: &App, area: Rect, header: &str, image: &RgbaImage) -> Option<Rect> {
+    let header = Line::from(Span::styled(
+        header.to_string(),
+        Style::default().fg(app.config.colors.status),
+    ));
+    f.render_widget(Paragraph::new(header), area);
+
+    // Leave the last row free: drawing a graphic there can scroll the screen
+    let image_area = Rect {
+        y: area.y + 2,
+        height: area.height.saturating_sub(3),
+        ..area
+    };
+    if app.image_protocol != ImageProtocol::Halfblocks {
+        return Some(image_area);
+    }
+    let lines = graphics::halfblocks(image, image_area.width, image_area.height);
+    f.render_widget(Paragraph::new(lines), image_area);
+    None
+}
+
+/// The `?` overlay: every action with its current keys, grouped by
+/// category and filtered by the search query
+fn draw_help(f: &mut Frame, app: &App, area: Rect) {
+    let Some(help) = &app.help else {
+        return;
+    };
+    let colors = &app.config.colors;
+    let popup = centered_rect(area, 70, 80);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(colors.border))
+        .title(Span::styled(
+            " Help ",
+            Style::default().fg(colors.header).add_modifier(Modifier::BOLD),
+        ))
+        .title_bottom(" type to search · ↑/↓ scroll · Esc close ");
+    let inner = block.inner(popup);
+    f.render_widget(Clear, popup);
+    f.render_widget(block, popup);
+
+    let query = help.query.to_lowercase();
+    let entries: Vec<(Action, String)> = Action::ALL
+        .iter()
+        .map(|&action| (action, app.config.keymap.keys_for(action).join(", ")))
+        .filter(|(action, keys)| {
+            query.is_empty()
+                || [action.name(), action.description(), action.category(), keys.as_str()]
+                    .iter()
+                    .any(|field| field.to_lowercase().contains(&query))
+        })
+        .collect();
+    let key_width = entries
+        .iter()
+        .map(|(_, keys)| keys.chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(8, 24);
+    let description_width = entries
+        .iter()
+        .map(|(action, _)| action.description().chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut lines: Vec<Line> = Vec::new();
+    let mut category = "";
+    for (action, keys) in &entries {
+        if action.category() != category {
+            category = action.category();
+            if !lines.is_empty() {
+                lines.push(Line::from(""));
+            }
+            lines.push(Line::from(Span::styled(
+                category,
+                Style::default().fg(colors.directory).add_modifier(Modifier::BOLD),
+            )));
+        }
+        let keys = if keys.is_empty() { "(unbound)" } else { keys };
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("  {:<width$}  ", keys, width = key_width),
+                Style::default().fg(colors.selected),
+            ),
+            Span::raw(format!("{:<width$}", action.description(), width = description_width)),
+            Span::styled(
+                format!("  {}", action.name()),
+                Style::default().fg(colors.status),
+            ),
+        ]));
+    }
+    if entries.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No matching actions",
+            Style::default().fg(colors.status),
+        )));
+    }
+
+    // Search line stays fixed above the scrolling list
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(0)])
+        .split(inner);
+    let search = Line::from(vec![
+        Span::styled("Search: ", Style::default().fg(colors.status)),
+        Span::raw(help.query.as_str()),
+        Span::styled("█", Style::default().fg(colors.status)),
+    ]);
+    f.render_widget(Paragraph::new(search), chunks[0]);
+
+    let list_height = chunks[1].height as usize;
+    let scroll = help.scroll.min(lines.len().saturating_sub(list_height));
+    let visible: Vec<Line> = lines.into_iter().skip(scroll).take(list_height).collect();
+    f.render_widget(Paragraph::new(visible), chunks[1]);
+}
+
+/// The command palette: a query line above the fuzzy-matched entries,
+/// with matched characters highlighted and bound keys on the right
+fn draw_palette(f: &mut Frame, app: &App, area: Rect) {
+    let Some(palette) = &app.palette else {
+        return;
+    };
+    let colors = &app.config.colors;
+    let matches = palette.matches();
+
+    let (title, popup) = if palette.is_theme_picker() || palette.is_outline() {
+        // Over the tree, leaving the preview visible to judge each theme by
+        // or to show each heading in
+        let width = app.tree_width(area.width).clamp(30.min(area.width), area.width);
+        let title = if palette.is_outline() { " Outline " } else { " Themes " };
+        (title, Rect::new(area.x, area.y, width, area.height.saturating_sub(1)))
+    } else {
+        // Anchor near the top like most editors' palettes
+        let mut popup = centered_rect(area, 60, 60);
+        popup.y = area.y + area.height / 8;
+        (" Commands ", popup)
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(colors.border))
+        .title(Span::styled(
+            title,
+            Style::default().fg(colors.header).add_modifier(Modifier::BOLD),
+        ))
+        .title_bottom(format!(" {} of {} ", matches.len(), palette.entry_count()));
+    let inner = block.inner(popup);
+    f.render_widget(Clear, popup);
+    f.render_widget(block, popup);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(0)])
+        .split(inner);
+    let prompt = Line::from(vec![
+        Span::styled("> ", Style::default().fg(colors.selected)),
+        Span::raw(palette.query.as_str()),
+        Span::styled("█", Style::default().fg(colors.status)),
+    ]);
+    f.render_widget(Paragraph::new(prompt), chunks[0]);
+
+    // Keep the selection in view
+    let list_height = chunks[1].height as usize;
+    let offset = palette.selected.saturating_sub(list_height.saturating_sub(1));
+    let width = chunks[1].width as usize;
+
+    let lines: Vec<Line> = matches
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(list_height)
+        .map(|(i, m)| {
+            let is_selected = i == palette.selected;
+            let base = if is_selected {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            let marker = if m.entry.active { "● " } else { "  " };
+            let mut spans = vec![Span::styled(marker, base.fg(colors.selected))];
+            for (ci, c) in m.entry.label.chars().enumerate() {
+                let style = if m.positions.contains(&ci) {
+                    base.fg(colors.selected).add_modifier(Modifier::BOLD)
+                } else {
+                    base
+                };
+                spans.push(Span::styled(c.to_string(), style));
+            }
+            let used = 2 + m.entry.label.chars().count();
+            let keys = &m.entry.keys;
+            let padding = width.saturating_sub(used + keys.chars().count() + 1);
+            spans.push(Span::styled(" ".repeat(padding), base));
+            spans.push(Span::styled(format!("{} ", keys), base.fg(colors.status)));
+            Line::from(spans)
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), chunks[1]);
+}
+
+/// A rectangle of the given percentage size centered in `area`
+fn centered_rect(area: Rect, width_percent: u16, height_percent: u16) -> Rect {
+    let width = area.width * width_percent / 100;
+    let height = area.height * height_percent / 100;
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// Hex dump with a fixed header line; only the visible rows are read,
+/// unless the bytes are already in memory
+fn draw_hex(
+    f: &mut Frame,
+    app: &App,
+    area: Rect,
+    path: &Path,
+    header: &str,
+    bytes: Option<&[u8]>,
+    total_rows: usize,
+) {
+    let rows = area.height.saturating_sub(1) as usize;
+    let scroll = app.preview_scroll.min(total_rows.saturating_sub(rows));
+
+    let mut lines = vec![Line::from(Span::styled(
+        header.to_string(),
+        Style::default().fg(app.config.colors.status),
+    ))];
+    match bytes {
+        Some(bytes) => {
+            let start = (scroll * binary::BYTES_PER_ROW).min(bytes.len());
+            let end = (start + rows * binary::BYTES_PER_ROW).min(bytes.len());
+            lines.extend(binary::hex_lines(&bytes[start..end], start as u64));
+        }
+        None => match binary::hex_dump(app.vfs.as_ref(), path, scroll, rows) {
+            Ok(dump) => lines.extend(dump),
+            Err(e) => lines.push(Line::from(format!("Error: {}", e))),
+        },
+    }
+
+    f.render_widget(Paragraph::new(lines), area);
 }
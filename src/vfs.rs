@@ -0,0 +1,337 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::archive::{self, ArchiveKind, ArchiveVfs, Entry};
+
+/// File information returned by a `Vfs`
+#[derive(Clone, Debug, Default)]
+pub struct Metadata {
+    pub is_dir: bool,
+    /// An archive file that `read_dir` can list like a directory
+    pub is_archive: bool,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    /// Identity of the file on its device (the inode on Unix), to notice
+    /// when a path is replaced by a new file
+    pub id: Option<u64>,
+}
+
+/// One entry of a directory listing
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    pub name: String,
+    pub path: PathBuf,
+    /// Type of the entry itself, without following symlinks
+    pub is_dir: bool,
+    pub is_symlink: bool,
+}
+
+/// The filesystem operations the tree and the previewer need, so they can
+/// browse things other than local directories (archives, in-memory trees,
+/// remote hosts)
+pub trait Vfs: Send + Sync {
+    /// Entries of a directory, in no particular order
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>>;
+
+    /// Metadata, following symlinks
+    fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+    /// Up to `len` bytes starting at `offset`
+    fn read_range(&self, path: &Path, offset: u64, len: u64) -> io::Result<Vec<u8>>;
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Whether `path` is on the local disk, where tools like gitignore
+    /// matching can look at it directly
+    fn is_local(&self, _path: &Path) -> bool {
+        false
+    }
+
+    /// The start of a file, up to `limit` bytes
+    fn read(&self, path: &Path, limit: u64) -> io::Result<Vec<u8>> {
+        self.read_range(path, 0, limit)
+    }
+
+    /// Every entry of a file whose metadata has `is_archive` set
+    fn list_archive(&self, _path: &Path) -> io::Result<Arc<Vec<Entry>>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "archives can't be listed here",
+        ))
+    }
+}
+
+/// The local filesystem. Paths that continue past an archive file (like
+/// `src.zip/lib/main.rs`) are served from inside the archive.
+#[derive(Default)]
+pub struct LocalVfs {
+    archives: ArchiveVfs,
+}
+
+impl Vfs for LocalVfs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        if self.in_archive(path) || self.is_archive(path) {
+            return self.archives.read_dir(path);
+        }
+        fs::read_dir(path)?
+            .map(|entry| {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+                Ok(DirEntry {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    path: entry.path(),
+                    is_dir: file_type.is_dir(),
+                    is_symlink: file_type.is_symlink(),
+                })
+            })
+            .collect()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        match fs::metadata(path) {
+            Ok(m) => Ok(local_metadata(path, &m)),
+            Err(_) if self.in_archive(path) => self.archives.metadata(path),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn read_range(&self, path: &Path, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        if self.in_archive(path) {
+            return self.archives.read_range(path, offset, len);
+        }
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buf = Vec::new();
+        file.take(len).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn is_local(&self, path: &Path) -> bool {
+        !self.in_archive(path)
+    }
+
+    fn list_archive(&self, path: &Path) -> io::Result<Arc<Vec<Entry>>> {
+        archive::list(path)
+    }
+}
+
+impl LocalVfs {
+    fn in_archive(&self, path: &Path) -> bool {
+        archive::split_path(path).is_some()
+    }
+
+    fn is_archive(&self, path: &Path) -> bool {
+        ArchiveKind::detect(path).is_some() && path.is_file()
+    }
+}
+
+fn local_metadata(path: &Path, m: &fs::Metadata) -> Metadata {
+    Metadata {
+        is_dir: m.is_dir(),
+        is_archive: m.is_file() && ArchiveKind::detect(path).is_some(),
+        len: m.len(),
+        modified: m.modified().ok(),
+        id: file_id(m),
+    }
+}
+
+#[cfg(unix)]
+fn file_id(m: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(m.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_m: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+enum MemoryNode {
+    Dir,
+    File(Vec<u8>),
+    Symlink(PathBuf),
+}
+
+/// A filesystem held in memory, so the tree and the previewer can be tested
+/// without touching the disk. Parent directories are created as files are
+/// added.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryVfs {
+    nodes: std::collections::BTreeMap<PathBuf, MemoryNode>,
+    modified: Option<SystemTime>,
+}
+
+#[cfg(test)]
+impl MemoryVfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Modification time reported for every entry
+    pub fn set_modified(&mut self, modified: SystemTime) {
+        self.modified = Some(modified);
+    }
+
+    pub fn add_dir(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        self.add_parents(&path);
+        self.nodes.insert(path, MemoryNode::Dir);
+    }
+
+    pub fn add_file(&mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) {
+        let path = path.into();
+        self.add_parents(&path);
+        self.nodes.insert(path, MemoryNode::File(contents.into()));
+    }
+
+    pub fn add_symlink(&mut self, path: impl Into<PathBuf>, target: impl Into<PathBuf>) {
+        let path = path.into();
+        self.add_parents(&path);
+        self.nodes.insert(path, MemoryNode::Symlink(target.into()));
+    }
+
+    fn add_parents(&mut self, path: &Path) {
+        for parent in path.ancestors().skip(1) {
+            self.nodes.entry(parent.to_path_buf()).or_insert(MemoryNode::Dir);
+        }
+    }
+
+    fn node(&self, path: &Path) -> io::Result<&MemoryNode> {
+        self.nodes
+            .get(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such file or directory"))
+    }
+
+    /// Follow symlinks (relative targets resolve against the link's
+    /// directory), giving up on loops
+    fn resolve(&self, path: &Path) -> io::Result<(PathBuf, &MemoryNode)> {
+        let mut path = path.to_path_buf();
+        for _ in 0..40 {
+            match self.node(&path)? {
+                MemoryNode::Symlink(target) => {
+                    path = path.parent().unwrap_or(Path::new("")).join(target);
+                }
+                node => return Ok((path, node)),
+            }
+        }
+        Err(io::Error::other("too many levels of symbolic links"))
+    }
+
+    fn node_metadata(&self, node: &MemoryNode) -> Metadata {
+        Metadata {
+            is_dir: matches!(node, MemoryNode::Dir),
+            is_archive: false,
+            len: match node {
+                MemoryNode::File(data) => data.len() as u64,
+                _ => 0,
+            },
+            modified: self.modified,
+            id: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Vfs for MemoryVfs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let (dir, node) = self.resolve(path)?;
+        if !matches!(node, MemoryNode::Dir) {
+            return Err(io::Error::new(io::ErrorKind::NotADirectory, "not a directory"));
+        }
+        Ok(self
+            .nodes
+            .iter()
+            .filter(|(p, _)| p.parent() == Some(dir.as_path()) && **p != dir)
+            .map(|(p, node)| DirEntry {
+                name: p
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                path: path.join(p.file_name().unwrap_or_default()),
+                is_dir: matches!(node, MemoryNode::Dir),
+                is_symlink: matches!(node, MemoryNode::Symlink(_)),
+            })
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let (_, node) = self.resolve(path)?;
+        Ok(self.node_metadata(node))
+    }
+
+    fn read_range(&self, path: &Path, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        match self.resolve(path)?.1 {
+            MemoryNode::File(data) => {
+                let start = (offset as usize).min(data.len());
+                let end = start.saturating_add(len as usize).min(data.len());
+                Ok(data[start..end].to_vec())
+            }
+            _ => Err(io::Error::new(io::ErrorKind::IsADirectory, "is a directory")),
+        }
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.node(path)? {
+            MemoryNode::Symlink(target) => Ok(target.clone()),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "not a symbolic link")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_vfs_lists_children_and_creates_parents() {
+        let mut vfs = MemoryVfs::new();
+        vfs.add_file("/root/src/main.rs", "fn main() {}");
+        vfs.add_dir("/root/docs");
+
+        let mut names: Vec<(String, bool)> = vfs
+            .read_dir(Path::new("/root"))
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.name, e.is_dir))
+            .collect();
+        names.sort();
+        assert_eq!(names, [("docs".to_string(), true), ("src".to_string(), true)]);
+
+        let metadata = vfs.metadata(Path::new("/root/src/main.rs")).unwrap();
+        assert!(!metadata.is_dir);
+        assert_eq!(metadata.len, 12);
+        assert!(vfs.read_dir(Path::new("/root/src/main.rs")).is_err());
+    }
+
+    #[test]
+    fn memory_vfs_reads_ranges_and_follows_symlinks() {
+        let mut vfs = MemoryVfs::new();
+        vfs.add_file("/root/data.txt", "hello world");
+        vfs.add_symlink("/root/link.txt", "data.txt");
+        vfs.add_symlink("/root/broken", "missing");
+
+        assert_eq!(vfs.read_range(Path::new("/root/link.txt"), 6, 100).unwrap(), b"world");
+        assert_eq!(vfs.read(Path::new("/root/data.txt"), 5).unwrap(), b"hello");
+        assert!(vfs.read_range(Path::new("/root/data.txt"), 50, 5).unwrap().is_empty());
+
+        assert!(vfs.metadata(Path::new("/root/broken")).is_err());
+        assert_eq!(vfs.read_link(Path::new("/root/broken")).unwrap(), Path::new("missing"));
+
+        let link = vfs
+            .read_dir(Path::new("/root"))
+            .unwrap()
+            .into_iter()
+            .find(|e| e.name == "link.txt")
+            .unwrap();
+        assert!(link.is_symlink && !link.is_dir);
+    }
+}
@@ -0,0 +1,157 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::preview::{PreviewContent, Previewer};
+use crate::vfs::Vfs;
+
+struct Request {
+    id: u64,
+    path: PathBuf,
+    previewer: Previewer,
+    vfs: Arc<dyn Vfs>,
+}
+
+/// A preview (or a placeholder for one) produced by the worker
+pub struct Response {
+    pub id: u64,
+    pub content: (PreviewContent, usize),
+    /// False for a placeholder that a better result will replace
+    pub done: bool,
+}
+
+/// The preview being generated, which can hand out a placeholder early and
+/// should stop as soon as a newer request supersedes it
+pub struct Job {
+    id: u64,
+    latest: Arc<AtomicU64>,
+    responses: Sender<Response>,
+}
+
+impl Job {
+    pub fn is_cancelled(&self) -> bool {
+        self.latest.load(Ordering::Relaxed) != self.id
+    }
+
+    /// Show `content` until the final result is ready
+    pub fn placeholder(&self, content: PreviewContent, lines: usize) {
+        if !self.is_cancelled() {
+            let _ = self.responses.send(Response {
+                id: self.id,
+                content: (content, lines),
+                done: false,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+impl Job {
+    /// A job that's never superseded, for calling the previewer directly.
+    /// Placeholders go nowhere.
+    pub fn standalone() -> Job {
+        let (responses, _) = mpsc::channel();
+        Job {
+            id: 0,
+            latest: Arc::new(AtomicU64::new(0)),
+            responses,
+        }
+    }
+}
+
+/// Generates previews on a background thread so reading and highlighting
+/// large files doesn't block the UI. Each request gets an id; results of
+/// anything but the latest request are dropped.
+pub struct PreviewWorker {
+    requests: Sender<Request>,
+    responses: Receiver<Response>,
+    latest: Arc<AtomicU64>,
+    /// Id of the request whose final result hasn't arrived yet
+    in_flight: Option<u64>,
+}
+
+impl PreviewWorker {
+    pub fn new() -> Self {
+        let (requests, request_rx) = mpsc::channel::<Request>();
+        let (response_tx, responses) = mpsc::channel();
+        let latest = Arc::new(AtomicU64::new(0));
+
+        let worker_latest = latest.clone();
+        thread::spawn(move || {
+            while let Ok(mut request) = request_rx.recv() {
+                // Only the newest of the queued requests matters
+                while let Ok(newer) = request_rx.try_recv() {
+                    request = newer;
+                }
+                let job = Job {
+                    id: request.id,
+                    latest: worker_latest.clone(),
+                    responses: response_tx.clone(),
+                };
+                if job.is_cancelled() {
+                    continue;
+                }
+                let content = request.previewer.preview(&request.vfs, &request.path, &job);
+                if !job.is_cancelled() {
+                    let _ = response_tx.send(Response {
+                        id: request.id,
+                        content,
+                        done: true,
+                    });
+                }
+            }
+        });
+
+        Self {
+            requests,
+            responses,
+            latest,
+            in_flight: None,
+        }
+    }
+
+    /// Start previewing `path`, superseding any earlier request
+    pub fn request(&mut self, previewer: Previewer, vfs: Arc<dyn Vfs>, path: &Path) {
+        let id = self.cancel();
+        self.in_flight = Some(id);
+        let _ = self.requests.send(Request {
+            id,
+            path: path.to_path_buf(),
+            previewer,
+            vfs,
+        });
+    }
+
+    /// Abandon the current request, if any. Returns the next request id.
+    pub fn cancel(&mut self) -> u64 {
+        self.in_flight = None;
+        self.latest.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// A result of the latest request, waiting up to `timeout` for one
+    pub fn recv(&mut self, timeout: Duration) -> Option<Response> {
+        let deadline = Instant::now() + timeout;
+        while self.in_flight.is_some() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let Ok(response) = self.responses.recv_timeout(remaining) else {
+                return None;
+            };
+            if Some(response.id) != self.in_flight {
+                continue;
+            }
+            if response.done {
+                self.in_flight = None;
+            }
+            return Some(response);
+        }
+        None
+    }
+
+    /// Whether a preview is being generated
+    pub fn is_busy(&self) -> bool {
+        self.in_flight.is_some()
+    }
+}